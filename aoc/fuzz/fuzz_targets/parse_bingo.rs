@@ -0,0 +1,12 @@
+#![no_main]
+use aoc::commands::day4a::{BingoBoard, Numbers};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        let _ = s.parse::<Numbers>();
+        if let Ok(board) = s.parse::<BingoBoard>() {
+            let _ = board.to_string();
+        }
+    }
+});
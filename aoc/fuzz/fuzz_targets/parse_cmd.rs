@@ -0,0 +1,13 @@
+#![no_main]
+use aoc::commands::day2::{Cmd, Program};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Ok(cmd) = s.parse::<Cmd>() {
+            // Commands print in the syntax they are parsed from
+            assert_eq!(cmd.to_string().parse::<Cmd>().unwrap(), cmd);
+        }
+        let _ = s.parse::<Program>();
+    }
+});
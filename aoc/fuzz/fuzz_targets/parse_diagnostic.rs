@@ -0,0 +1,11 @@
+#![no_main]
+use aoc::commands::day3a::DiagnosticReportLine;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Ok(line) = s.parse::<DiagnosticReportLine>() {
+            assert_eq!(line.to_string(), s);
+        }
+    }
+});
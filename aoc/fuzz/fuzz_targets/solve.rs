@@ -0,0 +1,23 @@
+#![no_main]
+//! Runs a whole day on the input, the first byte picks the day.
+use aoc::{commands::CommandImpl, Opts};
+use clap::Parser;
+use libfuzzer_sys::fuzz_target;
+
+const DAYS: [&str; 12] = [
+    "day1", "day1b", "day2", "day2b", "day3a", "day3b", "day4a", "day4b", "day5a", "day5b",
+    "day6a", "day6b",
+];
+
+fuzz_target!(|data: &[u8]| {
+    let (day, input) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let day = DAYS[*day as usize % DAYS.len()];
+    let path = std::env::temp_dir().join(format!("aoc-fuzz-{}.txt", std::process::id()));
+    std::fs::write(&path, input).unwrap();
+    let opts = Opts::try_parse_from(["aoc", day, "--input", path.to_str().unwrap()]).unwrap();
+    // Errors are expected for bad input, only panics are bugs
+    let _ = opts.subcommand.main();
+});
@@ -1,10 +1,13 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-    path::PathBuf,
+    error::Error,
+    fmt,
+    io::BufRead,
+    path::{Path, PathBuf},
 };
 
-use clap::Parser;
+use clap::{ArgEnum, Parser};
+
+use crate::utils::open_file;
 
 use super::{CommandImpl, DynError};
 
@@ -12,27 +15,380 @@ use super::{CommandImpl, DynError};
 pub struct Day1 {
     #[clap(long, short)]
     input: PathBuf,
+    /// Number of depths summed into each window
+    #[clap(long, short, default_value = "1")]
+    window: usize,
+    /// Change between consecutive windows to count
+    #[clap(long, arg_enum, default_value = "increase")]
+    compare: Compare,
+    /// Report runs, jumps, plateaus and a sparkline of the window sums instead
+    #[clap(long)]
+    analytics: bool,
+    /// Number of characters in the analytics sparkline
+    #[clap(long, default_value = "60")]
+    sparkline_width: usize,
 }
 
 impl CommandImpl for Day1 {
     fn main(&self) -> Result<(), DynError> {
-        let reader = File::open(&self.input).map(BufReader::new)?;
-        let mut depths = vec![];
-        for line in reader.lines() {
-            let line = line?;
-            depths.push(line.parse::<usize>()?);
-        }
-        let mut depth_increases = 0;
-        let mut prev_depth = None;
-        for depth in depths {
-            if let Some(prev) = prev_depth {
-                if depth > prev {
-                    depth_increases += 1;
+        if self.analytics {
+            let mut stats = SonarStats::default();
+            let mut profile = Sparkline::new(self.sparkline_width);
+            for_each_sum(&self.input, self.window, |sum| {
+                stats.push(sum);
+                profile.push(sum);
+            })?;
+            print!("{}", stats);
+            println!("Profile: {}", profile);
+            return Ok(());
+        }
+        count_and_report(&self.input, self.window, self.compare)
+    }
+}
+
+/// Stream the depths at `path` through a window of `size` and print how many times the sum
+/// changed by `compare`
+pub fn count_and_report(path: &Path, size: usize, compare: Compare) -> Result<(), DynError> {
+    let mut count = 0;
+    let mut prev_sum = None;
+    for_each_sum(path, size, |sum| {
+        if let Some(prev) = prev_sum {
+            if compare.matches(prev, sum) {
+                count += 1;
+            }
+        }
+        prev_sum = Some(sum);
+    })?;
+    println!("Total depth {}: {}", compare.label(), count);
+    Ok(())
+}
+
+/// Call `f` with the sum of every full window of `size` depths read from `path`.
+///
+/// Depths may be negative, and are summed as `i128` so a window of any practical size can't
+/// overflow.
+pub fn for_each_sum(path: &Path, size: usize, mut f: impl FnMut(i128)) -> Result<(), DynError> {
+    let reader = open_file(path)?;
+    let mut window = SlidingWindow::new(size)?;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let depth = line
+            .trim()
+            .parse::<i64>()
+            .map_err(|e| DepthError::at(i + 1, format!("invalid depth {:?}, {}", line, e)))?;
+        if let Some(sum) = window.push(depth).map_err(|e| DepthError::at(i + 1, e))? {
+            f(sum);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct DepthError {
+    msg: String,
+}
+
+impl DepthError {
+    fn at(line: usize, msg: impl fmt::Display) -> Self {
+        DepthError { msg: format!("line {}: {}", line, msg) }
+    }
+}
+
+impl Error for DepthError {}
+
+impl fmt::Display for DepthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error for command: {}", self.msg)
+    }
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Compare {
+    Increase,
+    Decrease,
+    Equal,
+}
+
+impl Compare {
+    pub fn matches(self, prev: i128, next: i128) -> bool {
+        match self {
+            Compare::Increase => next > prev,
+            Compare::Decrease => next < prev,
+            Compare::Equal => next == prev,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Compare::Increase => "increases",
+            Compare::Decrease => "decreases",
+            Compare::Equal => "repeats",
+        }
+    }
+}
+
+/// The sum of the last `size` depths, kept in a ring buffer so input can be streamed
+#[derive(Debug, Clone)]
+pub struct SlidingWindow {
+    depths: Vec<i64>,
+    /// Index of the oldest depth once the buffer is full
+    next: usize,
+    sum: i128,
+    size: usize,
+}
+
+impl SlidingWindow {
+    pub fn new(size: usize) -> Result<Self, DynError> {
+        if size == 0 {
+            return Err("Window size must be at least 1".into());
+        }
+        Ok(Self { depths: Vec::with_capacity(size), next: 0, sum: 0, size })
+    }
+
+    /// Add a depth, returning the window sum once the window is full
+    pub fn push(&mut self, depth: i64) -> Result<Option<i128>, DepthError> {
+        let oldest = if self.depths.len() < self.size {
+            self.depths.push(depth);
+            0
+        } else {
+            let oldest = std::mem::replace(&mut self.depths[self.next], depth);
+            self.next = (self.next + 1) % self.size;
+            oldest
+        };
+        self.sum = (self.sum - oldest as i128)
+            .checked_add(depth as i128)
+            .ok_or_else(|| DepthError { msg: String::from("window sum overflowed") })?;
+        Ok((self.depths.len() == self.size).then(|| self.sum))
+    }
+}
+
+/// A run of consecutive readings, `start` is the 1-based index of its first reading
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Run {
+    pub start: usize,
+    pub len: usize,
+}
+
+/// Statistics over a series of readings, updated one reading at a time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SonarStats {
+    pub readings: usize,
+    /// Longest strictly increasing run
+    pub longest_rise: Run,
+    /// Longest strictly decreasing run
+    pub longest_fall: Run,
+    /// Largest change between consecutive readings and the 1-based index of the later reading
+    pub largest_jump: Option<(u128, usize)>,
+    /// Number of runs of two or more equal readings
+    pub plateaus: usize,
+    prev: Option<i128>,
+    rise: Run,
+    fall: Run,
+    flat: usize,
+}
+
+impl SonarStats {
+    pub fn push(&mut self, reading: i128) {
+        self.readings += 1;
+        let index = self.readings;
+        let restart = Run { start: index, len: 1 };
+        match self.prev {
+            None => {
+                self.rise = restart;
+                self.fall = restart;
+                self.flat = 1;
+            }
+            Some(prev) => {
+                let jump = (reading - prev).unsigned_abs();
+                if self.largest_jump.map_or(true, |(largest, _)| jump > largest) {
+                    self.largest_jump = Some((jump, index));
+                }
+                // A run of each kind continues from the previous reading or restarts at it
+                let from_prev = Run { start: index - 1, len: 2 };
+                self.rise = if reading > prev { extend(self.rise, from_prev) } else { restart };
+                self.fall = if reading < prev { extend(self.fall, from_prev) } else { restart };
+                self.flat = if reading == prev { self.flat + 1 } else { 1 };
+                if self.flat == 2 {
+                    self.plateaus += 1;
                 }
             }
-            prev_depth = Some(depth)
         }
-        println!("Total depth increases: {}", depth_increases);
+        if self.rise.len > self.longest_rise.len {
+            self.longest_rise = self.rise;
+        }
+        if self.fall.len > self.longest_fall.len {
+            self.longest_fall = self.fall;
+        }
+        self.prev = Some(reading);
+    }
+}
+
+/// Grow `run` by one reading, or start a new run if it has ended
+fn extend(run: Run, from_prev: Run) -> Run {
+    if run.len >= 2 {
+        Run { len: run.len + 1, ..run }
+    } else {
+        from_prev
+    }
+}
+
+impl fmt::Display for SonarStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Readings: {}", self.readings)?;
+        for (name, run) in [("rise", self.longest_rise), ("fall", self.longest_fall)] {
+            writeln!(f, "Longest {}: {} readings from reading {}", name, run.len, run.start)?;
+        }
+        match self.largest_jump {
+            Some((jump, index)) => writeln!(f, "Largest jump: {} at reading {}", jump, index)?,
+            None => writeln!(f, "Largest jump: none")?,
+        }
+        writeln!(f, "Plateaus: {}", self.plateaus)
+    }
+}
+
+/// Draw `readings` as `width` block characters, averaging the readings that fall in each one
+pub fn sparkline(readings: &[i128], width: usize) -> String {
+    let mut sparkline = Sparkline::new(width);
+    readings.iter().for_each(|&r| sparkline.push(r));
+    sparkline.to_string()
+}
+
+/// A sparkline built one reading at a time in bounded memory.
+///
+/// Readings are summed into buckets of `span` consecutive readings. Whenever there are more
+/// than twice `width` buckets, neighbouring buckets are merged and `span` doubles, so only the
+/// last bucket is ever partly filled.
+#[derive(Debug, Clone)]
+pub struct Sparkline {
+    width: usize,
+    span: usize,
+    /// Sum and number of readings in each bucket
+    buckets: Vec<(f64, usize)>,
+}
+
+impl Sparkline {
+    pub fn new(width: usize) -> Self {
+        Self { width, span: 1, buckets: Vec::with_capacity(2 * width + 1) }
+    }
+
+    pub fn push(&mut self, reading: i128) {
+        if self.width == 0 {
+            return;
+        }
+        match self.buckets.last_mut() {
+            Some((sum, count)) if *count < self.span => {
+                *sum += reading as f64;
+                *count += 1;
+            }
+            _ => self.buckets.push((reading as f64, 1)),
+        }
+        if self.buckets.len() > 2 * self.width {
+            self.buckets = self
+                .buckets
+                .chunks(2)
+                .map(|pair| pair.iter().fold((0.0, 0), |(s, c), &(sum, n)| (s + sum, c + n)))
+                .collect();
+            self.span *= 2;
+        }
+    }
+}
+
+impl fmt::Display for Sparkline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+        let buckets = &self.buckets;
+        let width = self.width.min(buckets.len());
+        let means: Vec<f64> = (0..width)
+            .map(|i| {
+                let group = &buckets[i * buckets.len() / width..(i + 1) * buckets.len() / width];
+                let (sum, count) =
+                    group.iter().fold((0.0, 0), |(s, c), &(sum, n)| (s + sum, c + n));
+                sum / count as f64
+            })
+            .collect();
+        let low = means.iter().copied().fold(f64::INFINITY, f64::min);
+        let high = means.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        for mean in means {
+            let level = if high > low { (mean - low) / (high - low) * 7.0 } else { 0.0 };
+            write!(f, "{}", BLOCKS[level.round() as usize])?;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_sliding_window() {
+        let depths = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+        let count = |size, compare: Compare| {
+            let mut window = SlidingWindow::new(size).unwrap();
+            let sums: Vec<i128> = depths.iter().filter_map(|&d| window.push(d).unwrap()).collect();
+            sums.windows(2).filter(|w| compare.matches(w[0], w[1])).count()
+        };
+        assert_eq!(count(1, Compare::Increase), 7);
+        assert_eq!(count(3, Compare::Increase), 5);
+        assert_eq!(count(3, Compare::Equal), 1);
+        assert_eq!(count(3, Compare::Decrease), 1);
+        assert_eq!(count(10, Compare::Increase), 0);
+        assert_eq!(count(11, Compare::Increase), 0);
+        assert!(SlidingWindow::new(0).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_window_matches_slices(
+            depths in prop::collection::vec(any::<i64>(), 0..200),
+            size in 1..50usize,
+        ) {
+            let mut window = SlidingWindow::new(size).unwrap();
+            let sums: Vec<i128> = depths.iter().filter_map(|&d| window.push(d).unwrap()).collect();
+            let oracle: Vec<i128> =
+                depths.windows(size).map(|w| w.iter().map(|&d| d as i128).sum()).collect();
+            prop_assert_eq!(&sums, &oracle);
+
+            for compare in [Compare::Increase, Compare::Decrease, Compare::Equal] {
+                let count = |sums: &[i128]| {
+                    sums.windows(2).filter(|w| compare.matches(w[0], w[1])).count()
+                };
+                prop_assert_eq!(count(&sums), count(&oracle));
+            }
+        }
+    }
+
+    #[test]
+    fn test_signed_and_large_depths() {
+        let mut window = SlidingWindow::new(2).unwrap();
+        assert_eq!(window.push(-5).unwrap(), None);
+        assert_eq!(window.push(i64::MAX).unwrap(), Some(i64::MAX as i128 - 5));
+        assert_eq!(window.push(i64::MAX).unwrap(), Some(2 * i64::MAX as i128));
+        assert_eq!(window.push(i64::MIN).unwrap(), Some(-1));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut stats = SonarStats::default();
+        [5, 6, 7, 7, 7, 3, 2, 1, 0, 4, 4].iter().for_each(|&r| stats.push(r));
+        assert_eq!(stats.longest_rise, Run { start: 1, len: 3 });
+        assert_eq!(stats.longest_fall, Run { start: 5, len: 5 });
+        assert_eq!(stats.largest_jump, Some((4, 6)));
+        assert_eq!(stats.plateaus, 2);
+
+        assert_eq!(sparkline(&[1, 1, 8, 8], 2), "▁█");
+        assert_eq!(sparkline(&[1, 2, 3], 10), "▁▅█");
+        assert_eq!(sparkline(&[4, 4], 3), "▁▁");
+        assert_eq!(sparkline(&[], 3), "");
+        assert_eq!(sparkline(&[1, 2], 0), "");
+
+        // A long series keeps at most twice the width in buckets and still shows its shape
+        let mut profile = Sparkline::new(4);
+        (0..10_000).for_each(|r| profile.push(if r < 5_000 { 0 } else { 100 }));
+        assert!(profile.buckets.len() <= 8);
+        let line = profile.to_string();
+        assert_eq!(line.chars().count(), 4);
+        assert!(line.starts_with("▁▁") && line.ends_with('█'), "{}", line);
+    }
+}
@@ -1,54 +1,523 @@
-use std::{error::Error, fmt, path::PathBuf, str::FromStr};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    error::Error,
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use clap::Parser;
+use clap::{ArgEnum, Parser};
 
-use crate::utils::slurp_file;
+use crate::utils::read_to_string;
 
 use super::{CommandImpl, DynError};
 
 #[derive(Parser, Debug)]
 pub struct Day2 {
+    #[clap(long, short, required_unless_present = "target")]
+    input: Option<PathBuf>,
+    /// How `down` and `up` move the submarine
+    #[clap(long, arg_enum, default_value = "direct")]
+    model: Model,
+    /// Write the position after every command in this format
+    #[clap(long, arg_enum)]
+    trace: Option<TraceFormat>,
+    /// File to write the trace to, defaults to stdout
     #[clap(long, short)]
-    input: PathBuf,
+    output: Option<PathBuf>,
+    /// Print statistics about the whole course
+    #[clap(long)]
+    summary: bool,
+    /// Instead of running the input, write the shortest course reaching `x,depth`
+    #[clap(long)]
+    target: Option<Target>,
+    /// Largest magnitude the synthesized course may use
+    #[clap(long, default_value = "9")]
+    max_magnitude: i64,
+    /// Give up the synthesis after visiting this many positions
+    #[clap(long, default_value = "1000000")]
+    max_states: usize,
 }
 
 impl CommandImpl for Day2 {
     fn main(&self) -> Result<(), DynError> {
-        let cmds: Vec<Cmd> = slurp_file(&self.input)?;
-        let mut pos = Position::default();
-        cmds.iter().for_each(|cmd| pos.update(cmd));
-        eprintln!("{:?}", pos.x * pos.y);
+        let input = match (self.target, &self.input) {
+            (Some(target), _) => return self.synthesize(target),
+            (None, Some(input)) => input,
+            (None, None) => return Err("An input or a target is required".into()),
+        };
+        if self.trace.is_none() && !self.summary {
+            let pos = run_file(input, self.model)?;
+            println!("{}", pos.answer()?);
+            return Ok(());
+        }
+
+        let trace = trace_file(input, self.model)?;
+        if let Some(format) = self.trace {
+            match &self.output {
+                Some(path) => write_trace(&mut fs::File::create(path)?, &trace, format)?,
+                None => write_trace(&mut io::stdout().lock(), &trace, format)?,
+            }
+        }
+        // A trace on stdout must stay parseable, so the rest of the report goes to stderr
+        let mut report: Box<dyn Write> = match (self.trace, &self.output) {
+            (Some(_), None) => Box::new(io::stderr()),
+            _ => Box::new(io::stdout()),
+        };
+        if self.summary {
+            write!(report, "{}", CourseSummary::new(&trace))?;
+        }
+        let pos = trace.last().map_or_else(Position::default, |(_, pos)| *pos);
+        writeln!(report, "{}", pos.answer()?)?;
+        Ok(())
+    }
+}
+
+impl Day2 {
+    fn synthesize(&self, target: Target) -> Result<(), DynError> {
+        let solver = Solver { model: self.model, max_magnitude: self.max_magnitude };
+        let cmds = solver.solve(target, self.max_states)?;
+        let script: String = cmds.iter().map(|cmd| format!("{}\n", cmd)).collect();
+
+        // Replay the course as written to check it really reaches the target
+        let program: Program = script.parse()?;
+        let trace = trace_program(&program, self.model)?;
+        let pos = trace.last().map_or_else(Position::default, |(_, pos)| *pos);
+        if (pos.x, pos.y) != (target.x, target.depth) {
+            return Err(format!("Synthesized course ends at {},{}", pos.x, pos.y).into());
+        }
+
+        match &self.output {
+            Some(path) => fs::write(path, script)?,
+            None => print!("{}", script),
+        }
+        eprintln!("Reached {},{} in {} commands", pos.x, pos.y, cmds.len());
+        Ok(())
+    }
+}
+
+/// Run the course script at `path` from the starting position
+pub fn run_file(path: &Path, model: Model) -> Result<Position, DynError> {
+    let program: Program = read_to_string(path)?.parse()?;
+    let movement = model.movement();
+    let mut pos = Position::default();
+    program.run(&mut |cmd| movement.update(&mut pos, cmd))?;
+    Ok(pos)
+}
+
+/// Run the course script at `path`, keeping each command and the position after it
+pub fn trace_file(path: &Path, model: Model) -> Result<Vec<(Cmd, Position)>, DynError> {
+    let program: Program = read_to_string(path)?.parse()?;
+    Ok(trace_program(&program, model)?)
+}
+
+fn trace_program(program: &Program, model: Model) -> Result<Vec<(Cmd, Position)>, CmdError> {
+    let movement = model.movement();
+    let mut pos = Position::default();
+    let mut trace = vec![];
+    program.run(&mut |cmd| {
+        movement.update(&mut pos, cmd)?;
+        trace.push((*cmd, pos));
+        Ok(())
+    })?;
+    Ok(trace)
+}
+
+/// A horizontal position and depth to synthesize a course for, written `x,depth`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Target {
+    pub x: i64,
+    pub depth: i64,
+}
+
+impl FromStr for Target {
+    type Err = CmdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CmdError { msg: format!("Invalid target {:?}, expected x,depth", s) };
+        let (x, depth) = s.split_once(',').ok_or_else(invalid)?;
+        Ok(Target {
+            x: x.trim().parse().map_err(|_| invalid())?,
+            depth: depth.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Horizontal position, depth and aim
+type State = (i64, i64, i64);
+/// Fewest commands to reach each position, and the position and command it came from
+type Parents = HashMap<State, (usize, Option<(State, Cmd)>)>;
+
+/// Largest target coordinate and magnitude cap the solver accepts, which keeps its estimates
+/// well within range
+const MAX_TARGET: i64 = 1 << 40;
+const MAX_CAP: i64 = 1 << 20;
+
+/// A* search for the shortest list of `forward`, `back`, `down` and `up` commands reaching a
+/// target. Turns are never used, so the heading stays east.
+pub struct Solver {
+    pub model: Model,
+    pub max_magnitude: i64,
+}
+
+impl Solver {
+    pub fn solve(&self, target: Target, max_states: usize) -> Result<Vec<Cmd>, CmdError> {
+        if !(1..=MAX_CAP).contains(&self.max_magnitude) {
+            return Err(CmdError { msg: format!("Magnitude cap must be from 1 to {}", MAX_CAP) });
+        }
+        if target.x.abs() > MAX_TARGET || target.depth.abs() > MAX_TARGET {
+            return Err(CmdError {
+                msg: format!("Target coordinates must be within {} of the start", MAX_TARGET),
+            });
+        }
+        let movement = self.model.movement();
+        let key = |pos: &Position| (pos.x, pos.y, pos.aim);
+        let start = Position::default();
+        let mut best = Parents::new();
+        best.insert(key(&start), (0, None));
+        let mut open = BinaryHeap::new();
+        // Ties on the estimate go to the position furthest along, which heads straight for the
+        // target whenever the estimate is exact
+        open.push(Reverse((self.estimate(&start, target), Reverse(0), key(&start))));
+
+        while let Some(Reverse((_, Reverse(steps), state))) = open.pop() {
+            if best[&state].0 < steps {
+                continue;
+            }
+            if (state.0, state.1) == (target.x, target.depth) {
+                return Ok(self.path(&best, state));
+            }
+            if best.len() > max_states {
+                return Err(CmdError {
+                    msg: format!("No course found within {} positions", max_states),
+                });
+            }
+            for cmd in self.moves() {
+                let mut pos = Position { x: state.0, y: state.1, aim: state.2, ..start };
+                if movement.update(&mut pos, &cmd).is_err() {
+                    // Out of range positions can't be on a course worth synthesizing
+                    continue;
+                }
+                let next = key(&pos);
+                if best.get(&next).map_or(true, |(seen, _)| steps + 1 < *seen) {
+                    best.insert(next, (steps + 1, Some((state, cmd))));
+                    let estimate = steps + 1 + self.estimate(&pos, target);
+                    open.push(Reverse((estimate, Reverse(steps + 1), next)));
+                }
+            }
+        }
+        Err(CmdError { msg: "Target is unreachable".into() })
+    }
+
+    fn moves(&self) -> impl Iterator<Item = Cmd> {
+        (1..=self.max_magnitude)
+            .flat_map(|mag| [Cmd::Forward(mag), Cmd::Back(mag), Cmd::Down(mag), Cmd::Up(mag)])
+    }
+
+    /// Walk back from `state` to the start along the commands each position was reached by
+    fn path(&self, best: &Parents, state: State) -> Vec<Cmd> {
+        let mut cmds = vec![];
+        let mut state = state;
+        while let (_, Some((prev, cmd))) = best[&state] {
+            cmds.push(cmd);
+            state = prev;
+        }
+        cmds.reverse();
+        cmds
+    }
+
+    /// A lower bound on the commands left to reach `target` from `pos`
+    fn estimate(&self, pos: &Position, target: Target) -> usize {
+        // Positions far from the start can still overflow i64 here, so work in i128
+        let cap = self.max_magnitude as i128;
+        let dx = (target.x as i128 - pos.x as i128).abs();
+        let dy = (target.depth as i128 - pos.y as i128).abs();
+        let moves = (dx + cap - 1) / cap;
+        match self.model {
+            Model::Direct => usize::try_from(moves + (dy + cap - 1) / cap).unwrap_or(usize::MAX),
+            Model::Aim => {
+                // Every move changes depth by its distance times the aim it is made at, so
+                // `dy = aim * dx + sum((aim_i - aim) * f_i)`. With `j` of the `r` commands left
+                // changing aim, no aim is further than `j * cap` from the current one, and the
+                // other `r - j` moves travel at most `(r - j) * cap` between them.
+                let drift = (pos.aim as i128).saturating_mul(target.x as i128 - pos.x as i128);
+                let excess = (target.depth as i128 - pos.y as i128).saturating_sub(drift).abs();
+                let reach = |r: i128| {
+                    let most = r - moves;
+                    let j = (r / 2).min(most);
+                    j.saturating_mul(r - j).saturating_mul(cap * cap)
+                };
+                // Grow then bisect for the fewest commands that could cover the excess
+                let mut high = moves.max(1);
+                while reach(high) < excess {
+                    high = high.saturating_mul(2);
+                }
+                let mut low = moves;
+                while low < high {
+                    let mid = (low + high) / 2;
+                    if reach(mid) < excess {
+                        low = mid + 1;
+                    } else {
+                        high = mid;
+                    }
+                }
+                usize::try_from(low).unwrap_or(usize::MAX)
+            }
+        }
+    }
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    Csv,
+    Json,
+}
+
+/// Write one record per command with the 1-based step, the command, and the resulting position
+pub fn write_trace(
+    out: &mut impl Write,
+    trace: &[(Cmd, Position)],
+    format: TraceFormat,
+) -> io::Result<()> {
+    match format {
+        TraceFormat::Csv => {
+            writeln!(out, "step,command,x,depth,aim")?;
+            for (i, (cmd, pos)) in trace.iter().enumerate() {
+                writeln!(out, "{},{},{},{},{}", i + 1, cmd, pos.x, pos.y, pos.aim)?;
+            }
+        }
+        TraceFormat::Json => {
+            writeln!(out, "[")?;
+            for (i, (cmd, pos)) in trace.iter().enumerate() {
+                let sep = if i + 1 < trace.len() { "," } else { "" };
+                writeln!(
+                    out,
+                    r#"  {{"step": {}, "command": "{}", "x": {}, "depth": {}, "aim": {}}}{}"#,
+                    i + 1,
+                    cmd,
+                    pos.x,
+                    pos.y,
+                    pos.aim,
+                    sep
+                )?;
+            }
+            writeln!(out, "]")?;
+        }
+    }
+    Ok(())
+}
+
+/// Statistics over a traced course
+#[derive(Debug, Clone, PartialEq)]
+pub struct CourseSummary {
+    pub steps: usize,
+    pub max_depth: i64,
+    /// Number of commands that left the submarine above the surface
+    pub above_surface: usize,
+    /// Manhattan distance travelled, counting horizontal and vertical movement
+    pub distance: u128,
+    /// 1-based step and command that first took the submarine above the surface
+    pub first_invalid: Option<(usize, Cmd)>,
+}
+
+impl CourseSummary {
+    pub fn new(trace: &[(Cmd, Position)]) -> Self {
+        let mut summary = Self {
+            steps: trace.len(),
+            max_depth: 0,
+            above_surface: 0,
+            distance: 0,
+            first_invalid: None,
+        };
+        let mut prev = Position::default();
+        for (i, (cmd, pos)) in trace.iter().enumerate() {
+            summary.max_depth = summary.max_depth.max(pos.y);
+            // Each command changes a coordinate by at most an i64, so the differences fit
+            summary.distance += [pos.x - prev.x, pos.z - prev.z, pos.y - prev.y]
+                .iter()
+                .map(|d| d.unsigned_abs() as u128)
+                .sum::<u128>();
+            if pos.y < 0 {
+                summary.above_surface += 1;
+                summary.first_invalid.get_or_insert((i + 1, *cmd));
+            }
+            prev = *pos;
+        }
+        summary
+    }
+}
+
+impl fmt::Display for CourseSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Steps: {}", self.steps)?;
+        writeln!(f, "Max depth: {}", self.max_depth)?;
+        writeln!(f, "Steps above the surface: {}", self.above_surface)?;
+        writeln!(f, "Total distance: {}", self.distance)?;
+        match self.first_invalid {
+            Some((step, cmd)) => {
+                writeln!(f, "Invalid course: above the surface after step {} ({})", step, cmd)
+            }
+            None => writeln!(f, "Valid course"),
+        }
+    }
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Model {
+    /// `down` and `up` change depth directly
+    Direct,
+    /// `down` and `up` change aim, and moving changes depth by aim
+    Aim,
+}
+
+impl Model {
+    pub fn movement(self) -> &'static dyn Movement {
+        match self {
+            Model::Direct => &Direct,
+            Model::Aim => &Aim,
+        }
+    }
+}
+
+/// How each command changes the position of the submarine.
+///
+/// Fails if the position would no longer fit in 64 bits.
+pub trait Movement {
+    fn update(&self, pos: &mut Position, cmd: &Cmd) -> Result<(), CmdError>;
+}
+
+/// Part one, depth changes directly
+pub struct Direct;
+
+impl Movement for Direct {
+    fn update(&self, pos: &mut Position, cmd: &Cmd) -> Result<(), CmdError> {
+        match cmd {
+            Cmd::Forward(mag) => pos.advance(*mag)?,
+            Cmd::Back(mag) => pos.advance(in_range(mag.checked_neg())?)?,
+            Cmd::Down(mag) => pos.y = in_range(pos.y.checked_add(*mag))?,
+            Cmd::Up(mag) => pos.y = in_range(pos.y.checked_sub(*mag))?,
+            Cmd::TurnLeft => pos.heading = pos.heading.turn_left(),
+            Cmd::TurnRight => pos.heading = pos.heading.turn_right(),
+        }
         Ok(())
     }
 }
 
+/// Part two, depth changes by the aim for every unit moved
+pub struct Aim;
+
+impl Movement for Aim {
+    fn update(&self, pos: &mut Position, cmd: &Cmd) -> Result<(), CmdError> {
+        match cmd {
+            Cmd::Forward(mag) => {
+                pos.advance(*mag)?;
+                pos.y = in_range(pos.aim.checked_mul(*mag).and_then(|dy| pos.y.checked_add(dy)))?;
+            }
+            Cmd::Back(mag) => {
+                pos.advance(in_range(mag.checked_neg())?)?;
+                pos.y = in_range(pos.aim.checked_mul(*mag).and_then(|dy| pos.y.checked_sub(dy)))?;
+            }
+            Cmd::Down(mag) => pos.aim = in_range(pos.aim.checked_add(*mag))?,
+            Cmd::Up(mag) => pos.aim = in_range(pos.aim.checked_sub(*mag))?,
+            Cmd::TurnLeft => pos.heading = pos.heading.turn_left(),
+            Cmd::TurnRight => pos.heading = pos.heading.turn_right(),
+        }
+        Ok(())
+    }
+}
+
+/// The result of checked arithmetic on a coordinate, or an error if it overflowed
+fn in_range(value: Option<i64>) -> Result<i64, CmdError> {
+    value.ok_or_else(|| CmdError { msg: String::from("position does not fit in 64 bits") })
+}
+
+/// The direction `forward` moves in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Heading {
+    /// Along x, the starting heading
+    East,
+    /// Along z
+    South,
+    West,
+    North,
+}
+
+impl Heading {
+    fn turn_right(self) -> Self {
+        match self {
+            Heading::East => Heading::South,
+            Heading::South => Heading::West,
+            Heading::West => Heading::North,
+            Heading::North => Heading::East,
+        }
+    }
+
+    fn turn_left(self) -> Self {
+        self.turn_right().turn_right().turn_right()
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Position {
     pub x: i64,
     pub y: i64,
+    /// Distance to the right of the starting heading
+    pub z: i64,
+    /// Only changed by the aim model
+    pub aim: i64,
+    pub heading: Heading,
 }
 
 impl Position {
-    fn update(&mut self, cmd: &Cmd) {
-        match cmd {
-            Cmd::Forward(mag) => self.x += mag,
-            Cmd::Down(mag) => self.y += mag,
-            Cmd::Up(mag) => self.y -= mag,
+    /// The puzzle answer, horizontal position times depth
+    pub fn answer(&self) -> Result<i64, CmdError> {
+        self.x.checked_mul(self.y).ok_or_else(|| CmdError {
+            msg: format!("Answer {} * {} does not fit in 64 bits", self.x, self.y),
+        })
+    }
+
+    /// Move `mag` along the current heading
+    fn advance(&mut self, mag: i64) -> Result<(), CmdError> {
+        match self.heading {
+            Heading::East => self.x = in_range(self.x.checked_add(mag))?,
+            Heading::South => self.z = in_range(self.z.checked_add(mag))?,
+            Heading::West => self.x = in_range(self.x.checked_sub(mag))?,
+            Heading::North => self.z = in_range(self.z.checked_sub(mag))?,
         }
+        Ok(())
     }
 }
 
 impl Default for Position {
     fn default() -> Self {
-        Self { x: Default::default(), y: Default::default() }
+        Self { x: 0, y: 0, z: 0, aim: 0, heading: Heading::East }
     }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
-enum Cmd {
+pub enum Cmd {
     Forward(i64),
+    Back(i64),
     Down(i64),
     Up(i64),
+    TurnLeft,
+    TurnRight,
+}
+
+/// Formats in the same syntax the command is parsed from
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cmd::Forward(mag) => write!(f, "forward {}", mag),
+            Cmd::Back(mag) => write!(f, "back {}", mag),
+            Cmd::Down(mag) => write!(f, "down {}", mag),
+            Cmd::Up(mag) => write!(f, "up {}", mag),
+            Cmd::TurnLeft => write!(f, "turn left"),
+            Cmd::TurnRight => write!(f, "turn right"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -56,6 +525,12 @@ pub struct CmdError {
     msg: String,
 }
 
+impl CmdError {
+    fn at(line: usize, msg: impl fmt::Display) -> Self {
+        CmdError { msg: format!("line {}: {}", line, msg) }
+    }
+}
+
 impl Error for CmdError {}
 
 impl fmt::Display for CmdError {
@@ -73,17 +548,349 @@ impl FromStr for Cmd {
             .next()
             .ok_or(CmdError { msg: String::from("Missing Command") })?
             .to_ascii_lowercase();
-        let mag_part = parts
-            .next()
-            .ok_or(CmdError { msg: String::from("Missing Command Magnitude") })?
-            .parse::<i64>()
-            .map_err(|e| CmdError { msg: e.to_string() })?;
+        let arg_part =
+            parts.next().ok_or(CmdError { msg: String::from("Missing Command Magnitude") })?;
+
+        if cmd_part == "turn" {
+            return match arg_part.to_ascii_lowercase().as_ref() {
+                "left" => Ok(Cmd::TurnLeft),
+                "right" => Ok(Cmd::TurnRight),
+                _ => Err(CmdError { msg: "Invalid turn direction".into() }),
+            };
+        }
 
+        let mag_part = arg_part.parse::<i64>().map_err(|e| CmdError { msg: e.to_string() })?;
         match cmd_part.as_ref() {
             "forward" => Ok(Cmd::Forward(mag_part)),
+            "back" => Ok(Cmd::Back(mag_part)),
             "down" => Ok(Cmd::Down(mag_part)),
             "up" => Ok(Cmd::Up(mag_part)),
             _ => Err(CmdError { msg: "Invalid command".into() }),
         }
     }
 }
+
+/// A statement of a course script
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Cmd(Cmd),
+    /// `repeat N { ... }`
+    Repeat(u64, Vec<Stmt>),
+    /// Use of a macro defined with `macro NAME { ... }`, along with the line it was used on
+    Call(String, usize),
+}
+
+/// A course script.
+///
+/// Each command is a verb and argument, e.g. `forward 5`, `back 2` or `turn left`. Commands
+/// can be grouped into `repeat N { ... }` blocks, and named with `macro NAME { ... }` then used
+/// by writing `NAME`. Everything after a `#` on a line is a comment. Running stops with an
+/// error past `MAX_DEPTH` levels of nesting or `MAX_STEPS` commands and repeats.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Program {
+    statements: Vec<Stmt>,
+    macros: HashMap<String, Vec<Stmt>>,
+}
+
+/// Deepest nesting of blocks, or of macros calling macros, a script may use
+const MAX_DEPTH: usize = 64;
+/// Most commands and repeat iterations a script may run, so a huge `repeat` fails quickly
+const MAX_STEPS: usize = 1_000_000;
+
+impl Program {
+    /// Run every command in order, expanding repeats and macros as they are reached. An error
+    /// from `f` stops the run and is reported with the step and command it happened at.
+    pub fn run(&self, f: &mut impl FnMut(&Cmd) -> Result<(), CmdError>) -> Result<(), CmdError> {
+        let mut commands = 0;
+        let mut f = |cmd: &Cmd| {
+            commands += 1;
+            f(cmd).map_err(|e| CmdError { msg: format!("step {} ({}): {}", commands, cmd, e.msg) })
+        };
+        self.run_block(&self.statements, &mut vec![], &mut 0, &mut f)
+    }
+
+    fn run_block<'a>(
+        &'a self,
+        statements: &'a [Stmt],
+        calls: &mut Vec<&'a str>,
+        steps: &mut usize,
+        f: &mut impl FnMut(&Cmd) -> Result<(), CmdError>,
+    ) -> Result<(), CmdError> {
+        for statement in statements {
+            match statement {
+                Stmt::Cmd(cmd) => {
+                    count_step(steps)?;
+                    f(cmd)?;
+                }
+                Stmt::Repeat(times, body) => {
+                    for _ in 0..*times {
+                        count_step(steps)?;
+                        self.run_block(body, calls, steps, f)?;
+                    }
+                }
+                Stmt::Call(name, line) => {
+                    let body = self
+                        .macros
+                        .get(name)
+                        .ok_or_else(|| CmdError::at(*line, format!("Unknown macro {:?}", name)))?;
+                    if calls.contains(&name.as_str()) {
+                        return Err(CmdError::at(*line, format!("Macro {:?} calls itself", name)));
+                    }
+                    if calls.len() >= MAX_DEPTH {
+                        let msg = format!("Macros nested more than {} deep", MAX_DEPTH);
+                        return Err(CmdError::at(*line, msg));
+                    }
+                    calls.push(name);
+                    self.run_block(body, calls, steps, f)?;
+                    calls.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn count_step(steps: &mut usize) -> Result<(), CmdError> {
+    *steps += 1;
+    if *steps > MAX_STEPS {
+        return Err(CmdError {
+            msg: format!("Course runs more than {} commands and repeats", MAX_STEPS),
+        });
+    }
+    Ok(())
+}
+
+impl FromStr for Program {
+    type Err = CmdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let code = line.split('#').next().unwrap_or_default();
+            let code = code.replace('{', " { ").replace('}', " } ");
+            tokens.extend(code.split_ascii_whitespace().map(|t| (i + 1, t.to_string())));
+        }
+
+        let mut program = Program::default();
+        let mut tokens = tokens.into_iter().peekable();
+        program.statements = program.parse_block(&mut tokens, None, 0)?;
+        Ok(program)
+    }
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<(usize, String)>>;
+
+impl Program {
+    /// Parse statements up to the `}` closing the block opened on `opened`, or to the end of
+    /// input for the top level. `depth` counts the blocks enclosing this one.
+    fn parse_block(
+        &mut self,
+        tokens: &mut Tokens,
+        opened: Option<usize>,
+        depth: usize,
+    ) -> Result<Vec<Stmt>, CmdError> {
+        if depth > MAX_DEPTH {
+            let line = opened.unwrap_or_default();
+            return Err(CmdError::at(line, format!("Blocks nested more than {} deep", MAX_DEPTH)));
+        }
+        let mut statements = vec![];
+        while let Some((line, token)) = tokens.next() {
+            let keyword = token.to_ascii_lowercase();
+            match keyword.as_ref() {
+                "}" if opened.is_some() => return Ok(statements),
+                "}" => return Err(CmdError::at(line, "Unmatched }")),
+                "{" => return Err(CmdError::at(line, "Unexpected {")),
+                "repeat" => {
+                    let times = expect_arg(tokens, line, "repeat count")?;
+                    let times = times.parse::<u64>().map_err(|e| CmdError::at(line, e))?;
+                    expect_open(tokens, line)?;
+                    let body = self.parse_block(tokens, Some(line), depth + 1)?;
+                    statements.push(Stmt::Repeat(times, body));
+                }
+                "macro" => {
+                    let name = expect_arg(tokens, line, "macro name")?;
+                    if is_keyword(&name) {
+                        return Err(CmdError::at(line, format!("{:?} is a reserved word", name)));
+                    }
+                    expect_open(tokens, line)?;
+                    let body = self.parse_block(tokens, Some(line), depth + 1)?;
+                    if self.macros.insert(name.clone(), body).is_some() {
+                        return Err(CmdError::at(line, format!("Macro {:?} defined twice", name)));
+                    }
+                }
+                "forward" | "back" | "down" | "up" | "turn" => {
+                    let arg = expect_arg(tokens, line, "argument")?;
+                    let cmd = format!("{} {}", keyword, arg)
+                        .parse()
+                        .map_err(|e: CmdError| CmdError::at(line, e.msg))?;
+                    statements.push(Stmt::Cmd(cmd));
+                }
+                _ => statements.push(Stmt::Call(token, line)),
+            }
+        }
+        match opened {
+            Some(line) => Err(CmdError::at(line, "Block is never closed")),
+            None => Ok(statements),
+        }
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    let word = word.to_ascii_lowercase();
+    ["forward", "back", "down", "up", "turn", "repeat", "macro", "{", "}"].contains(&word.as_ref())
+}
+
+fn expect_arg(tokens: &mut Tokens, line: usize, what: &str) -> Result<String, CmdError> {
+    match tokens.next_if(|(l, t)| *l == line && t != "{" && t != "}") {
+        Some((_, arg)) => Ok(arg),
+        None => Err(CmdError::at(line, format!("Missing {}", what))),
+    }
+}
+
+fn expect_open(tokens: &mut Tokens, line: usize) -> Result<(), CmdError> {
+    match tokens.next_if(|(l, t)| *l == line && t == "{") {
+        Some(_) => Ok(()),
+        None => Err(CmdError::at(line, "Expected { on the same line")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_model(script: &str, model: Model) -> Result<Position, CmdError> {
+        let program: Program = script.parse()?;
+        let mut pos = Position::default();
+        program.run(&mut |cmd| model.movement().update(&mut pos, cmd))?;
+        Ok(pos)
+    }
+
+    fn run(script: &str) -> Result<Position, CmdError> {
+        run_model(script, Model::Direct)
+    }
+
+    #[test]
+    fn test_plain_commands() {
+        let example = "forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2";
+        let pos = run(example).unwrap();
+        assert_eq!((pos.x, pos.y), (15, 10));
+        let pos = run_model(example, Model::Aim).unwrap();
+        assert_eq!((pos.x, pos.y), (15, 60));
+    }
+
+    #[test]
+    fn test_trace_summary() {
+        let program: Program = "down 2\nforward 3\nup 5\nforward 1\ndown 4".parse().unwrap();
+        let trace = trace_program(&program, Model::Direct).unwrap();
+        let summary = CourseSummary::new(&trace);
+        assert_eq!(summary.max_depth, 2);
+        assert_eq!(summary.above_surface, 2);
+        assert_eq!(summary.distance, 15);
+        assert_eq!(summary.first_invalid, Some((3, Cmd::Up(5))));
+
+        let mut csv = vec![];
+        write_trace(&mut csv, &trace[..2], TraceFormat::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "step,command,x,depth,aim\n1,down 2,0,2,0\n2,forward 3,3,2,0\n"
+        );
+        let mut json = vec![];
+        write_trace(&mut json, &trace[..1], TraceFormat::Json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "[\n  {\"step\": 1, \"command\": \"down 2\", \"x\": 0, \"depth\": 2, \"aim\": 0}\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_solver() {
+        for (model, x, depth, len) in [
+            (Model::Direct, 15, 10, 4),
+            (Model::Direct, -3, 0, 1),
+            (Model::Aim, 15, 60, 3),
+            (Model::Aim, 0, 18, 3),
+            (Model::Aim, 2, -7, 3),
+        ] {
+            let solver = Solver { model, max_magnitude: 9 };
+            let cmds = solver.solve(Target { x, depth }, 100_000).unwrap();
+            let program = Program {
+                statements: cmds.iter().map(|c| Stmt::Cmd(*c)).collect(),
+                ..Program::default()
+            };
+            let trace = trace_program(&program, model).unwrap();
+            let end = trace.last().map(|(_, pos)| (pos.x, pos.y)).unwrap_or((0, 0));
+            assert_eq!((end, cmds.len()), ((x, depth), len), "{:?} {:?}", model, cmds);
+        }
+        assert_eq!("4, -2".parse::<Target>().unwrap(), Target { x: 4, depth: -2 });
+        assert!("4".parse::<Target>().is_err());
+
+        let solver = Solver { model: Model::Aim, max_magnitude: 9 };
+        let far = Target { x: i64::MAX, depth: 0 };
+        assert!(solver.solve(far, 10).unwrap_err().to_string().contains("within"));
+        let solver = Solver { model: Model::Aim, max_magnitude: i64::MAX };
+        assert!(solver.solve(Target { x: 1, depth: 1 }, 10).is_err());
+    }
+
+    #[test]
+    fn test_aim_back() {
+        let pos = run_model("down 2\nforward 3\nback 1", Model::Aim).unwrap();
+        assert_eq!((pos.x, pos.y, pos.aim), (2, 4, 2));
+    }
+
+    #[test]
+    fn test_language() {
+        let script = "
+            # Zig zag down
+            macro step {
+                forward 2 # comment after a command
+                down 1
+            }
+            repeat 3 { step }
+            back 1
+            turn right
+            forward 4
+            turn left
+            turn left
+            repeat 2 { repeat 2 { forward 1 } }
+        ";
+        let pos = run(script).unwrap();
+        assert_eq!((pos.x, pos.y, pos.z), (5, 3, 0));
+        assert_eq!(pos.heading, Heading::North);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(run("forward").unwrap_err().to_string().contains("line 1"));
+        assert!(run("repeat 2 {\nforward 1").unwrap_err().to_string().contains("never closed"));
+        assert!(run("dive").unwrap_err().to_string().contains("Unknown macro"));
+        assert!(run("macro a { a }\na").unwrap_err().to_string().contains("calls itself"));
+        assert!(run("turn around").is_err());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let err = run("forward 9223372036854775807\nforward 1").unwrap_err().to_string();
+        assert!(err.contains("step 2 (forward 1)"), "{}", err);
+        let err = run_model("down 5000000000\nforward 5000000000", Model::Aim).unwrap_err();
+        assert!(err.to_string().contains("step 2"), "{}", err);
+        assert!(run("back -9223372036854775808").is_err());
+        assert!(run_model("up 9223372036854775807\nup 2", Model::Aim).is_err());
+
+        let pos = run("forward 9223372036854775807\ndown 2").unwrap();
+        assert!(pos.answer().is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let nested = "repeat 1 {\n".repeat(100_000);
+        assert!(run(&nested).unwrap_err().to_string().contains("nested more than"));
+        let chain: String = (1..100).map(|i| format!("macro m{} {{ m{} }}\n", i, i - 1)).collect();
+        let chain = format!("macro m0 {{ forward 1 }}\n{}m99", chain);
+        assert!(run(&chain).unwrap_err().to_string().contains("nested more than"));
+
+        let huge = "repeat 18446744073709551615 { forward 1 }";
+        assert!(run(huge).unwrap_err().to_string().contains("more than"));
+        assert!(run("repeat 18446744073709551615 { }").is_err());
+        assert_eq!(run("repeat 1000 { repeat 400 { forward 1 } }").unwrap().x, 400_000);
+    }
+}
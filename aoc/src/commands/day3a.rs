@@ -1,6 +1,12 @@
-use std::{error::Error, fmt, path::PathBuf, str::FromStr};
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use clap::Parser;
+use num_bigint::BigUint;
 
 use crate::utils::slurp_file;
 
@@ -10,45 +16,73 @@ use super::{CommandImpl, DynError};
 pub struct Day3a {
     #[clap(long, short)]
     input: PathBuf,
+    /// Count columns over a transposed copy of the report using popcounts
+    #[clap(long)]
+    bit_sliced: bool,
+    /// Expected width of every line, defaults to the width of the first line
+    #[clap(long, short)]
+    width: Option<usize>,
 }
 
 impl CommandImpl for Day3a {
     fn main(&self) -> Result<(), DynError> {
-        let diagnostics: Vec<DiagnosticReportLine> = slurp_file(&self.input)?;
-
-        let epsilon_mask = !(u64::MAX << diagnostics[0].width);
-        let mut gamma: u64 = 0;
-        let mut epsilon: u64 = 0;
-        let mut mask = !(u64::MAX >> 1);
-
-        for _ in 0..u64::BITS {
-            let mut zeros = 0;
-            let mut ones = 0;
-            for DiagnosticReportLine { value, width: _ } in &diagnostics {
-                if (value & mask) > 0 {
-                    ones += 1;
-                } else {
-                    zeros += 1;
-                }
-            }
+        let (diagnostics, width) = read_report(&self.input, self.width)?;
 
-            if ones > zeros {
-                gamma |= mask;
-            } else {
-                epsilon |= mask;
-            }
-
-            mask = mask.rotate_right(1);
-        }
+        let counts = if self.bit_sliced {
+            BitSlicedReport::new(&diagnostics, width).counts()
+        } else {
+            ColumnCounts::from_lines(&diagnostics, width)
+        };
 
-        println!("Gamma: {}", gamma);
-        println!("Epislon: {}", epsilon & epsilon_mask);
-        println!("Answer: {}", gamma * (epsilon & epsilon_mask));
+        let (gamma, epsilon) = gamma_epsilon(&counts);
+        println!("Gamma: {} ({})", gamma.to_biguint(), gamma);
+        println!("Epislon: {} ({})", epsilon.to_biguint(), epsilon);
+        println!("Answer: {}", gamma.to_biguint() * epsilon.to_biguint());
 
         Ok(())
     }
 }
 
+/// Gamma takes the most common bit of each column and epsilon the least common, with ties
+/// going to epsilon
+pub fn gamma_epsilon(counts: &ColumnCounts) -> (DiagnosticReportLine, DiagnosticReportLine) {
+    let mut gamma = DiagnosticReportLine::zeros(counts.width());
+    let mut epsilon = DiagnosticReportLine::zeros(counts.width());
+    for i in 0..counts.width() {
+        if counts.ones(i) > counts.zeros(i) {
+            gamma.set_bit(i);
+        } else {
+            epsilon.set_bit(i);
+        }
+    }
+    (gamma, epsilon)
+}
+
+/// Read a report, checking it is not empty and every line has the same width, which is
+/// returned along with the lines.
+pub fn read_report(
+    path: impl AsRef<Path>,
+    width: Option<usize>,
+) -> Result<(Vec<DiagnosticReportLine>, usize), DynError> {
+    let diagnostics: Vec<DiagnosticReportLine> = slurp_file(path)?;
+    let expected = match (width, diagnostics.first()) {
+        (_, None) => {
+            return Err(DiagnosticError { msg: String::from("Diagnostic report is empty") }.into())
+        }
+        (Some(width), _) => width,
+        (None, Some(first)) => first.width(),
+    };
+    for (i, line) in diagnostics.iter().enumerate() {
+        if line.width() != expected {
+            return Err(DiagnosticError {
+                msg: format!("line {} has width {}, expected {}", i + 1, line.width(), expected),
+            }
+            .into());
+        }
+    }
+    Ok((diagnostics, expected))
+}
+
 #[derive(Debug, Clone)]
 pub struct DiagnosticError {
     msg: String,
@@ -62,19 +96,276 @@ impl fmt::Display for DiagnosticError {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// A line of the report as a bit vector of any width.
+///
+/// Bit 0 is the leftmost (most significant) character of the line.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DiagnosticReportLine {
-    value: u64,
-    width: u64,
+    words: Vec<u64>,
+    width: usize,
+}
+
+impl DiagnosticReportLine {
+    pub fn zeros(width: usize) -> Self {
+        Self { words: vec![0; (width + 63) / 64], width }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn bit(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (63 - i % 64)) != 0
+    }
+
+    pub fn set_bit(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (63 - i % 64);
+    }
+
+    pub fn to_biguint(&self) -> BigUint {
+        BigUint::parse_bytes(self.to_string().as_bytes(), 2).unwrap_or_default()
+    }
+
+    /// The index of every set bit in ascending order
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let offset = word.leading_zeros() as usize;
+                word &= !(1 << (63 - offset));
+                Some(w * 64 + offset)
+            })
+        })
+    }
+}
+
+/// Formats as the binary string the line was parsed from
+impl fmt::Display for DiagnosticReportLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..self.width {
+            write!(f, "{}", if self.bit(i) { '1' } else { '0' })?;
+        }
+        Ok(())
+    }
 }
 
 impl FromStr for DiagnosticReportLine {
     type Err = DiagnosticError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let width = s.len() as u64;
-        let value =
-            u64::from_str_radix(s, 2).map_err(|e| DiagnosticError { msg: e.to_string() })?;
-        Ok(DiagnosticReportLine { value, width })
+        let mut line = DiagnosticReportLine::zeros(s.len());
+        for (i, c) in s.chars().enumerate() {
+            match c {
+                '1' => line.set_bit(i),
+                '0' => (),
+                _ => {
+                    return Err(DiagnosticError {
+                        msg: format!("Invalid binary digit {:?} at column {}", c, i + 1),
+                    })
+                }
+            }
+        }
+        Ok(line)
+    }
+}
+
+/// The number of lines with a one in each column
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnCounts {
+    ones: Vec<usize>,
+    total: usize,
+}
+
+impl ColumnCounts {
+    /// Tally every column in a single pass, visiting only the set bits of each line.
+    pub fn from_lines(lines: &[DiagnosticReportLine], width: usize) -> Self {
+        let mut ones = vec![0; width];
+        for line in lines {
+            line.ones().for_each(|i| ones[i] += 1);
+        }
+        Self { ones, total: lines.len() }
+    }
+
+    pub fn width(&self) -> usize {
+        self.ones.len()
+    }
+
+    /// Total number of lines counted
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn ones(&self, column: usize) -> usize {
+        self.ones[column]
+    }
+
+    pub fn zeros(&self, column: usize) -> usize {
+        self.total - self.ones[column]
+    }
+}
+
+/// The report transposed so each column is a bit vector with one bit per line.
+///
+/// Counting a column is then a popcount over `lines / 64` words, and counting within a subset of
+/// lines is the same popcount after masking with that subset.
+#[derive(Clone, Debug)]
+pub struct BitSlicedReport {
+    columns: Vec<Vec<u64>>,
+    lines: usize,
+}
+
+impl BitSlicedReport {
+    pub fn new(lines: &[DiagnosticReportLine], width: usize) -> Self {
+        let words = (lines.len() + 63) / 64;
+        let mut columns = vec![vec![0; words]; width];
+        for (n, line) in lines.iter().enumerate() {
+            line.ones().for_each(|i| columns[i][n / 64] |= 1 << (n % 64));
+        }
+        Self { columns, lines: lines.len() }
+    }
+
+    pub fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// A mask holding every line
+    pub fn all_lines(&self) -> Vec<u64> {
+        let mut mask = vec![u64::MAX; (self.lines + 63) / 64];
+        if self.lines % 64 != 0 {
+            *mask.last_mut().unwrap() = (1 << (self.lines % 64)) - 1;
+        }
+        mask
+    }
+
+    /// The bit vector for `column`, bit `n` is set if line `n` has a one in that column
+    pub fn column(&self, column: usize) -> &[u64] {
+        &self.columns[column]
+    }
+
+    pub fn counts(&self) -> ColumnCounts {
+        let ones = self.columns.iter().map(|column| popcount(column)).collect();
+        ColumnCounts { ones, total: self.lines }
+    }
+
+    /// Counts over only the lines set in `mask`, which holds one bit per line
+    pub fn counts_within(&self, mask: &[u64]) -> ColumnCounts {
+        let ones = self
+            .columns
+            .iter()
+            .map(|column| column.iter().zip(mask).map(|(c, m)| (c & m).count_ones() as usize).sum())
+            .collect();
+        ColumnCounts { ones, total: popcount(mask) }
+    }
+}
+
+fn popcount(words: &[u64]) -> usize {
+    words.iter().map(|w| w.count_ones() as usize).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    const EXAMPLE: [&str; 12] = [
+        "00100", "11110", "10110", "10111", "10101", "01111", "00111", "11100", "10000", "11001",
+        "00010", "01010",
+    ];
+
+    #[test]
+    fn test_column_counts() {
+        let lines: Vec<DiagnosticReportLine> = EXAMPLE.iter().map(|l| l.parse().unwrap()).collect();
+        let counts = ColumnCounts::from_lines(&lines, 5);
+        assert_eq!(counts.ones, vec![7, 5, 8, 7, 5]);
+        assert_eq!(counts.zeros(1), 7);
+
+        let sliced = BitSlicedReport::new(&lines, 5);
+        assert_eq!(sliced.counts(), counts);
+        // Only the first and last lines
+        let within = sliced.counts_within(&[0b1000_0000_0001]);
+        assert_eq!(within.ones, vec![0, 1, 1, 1, 0]);
+        assert_eq!(within.total(), 2);
+    }
+
+    fn read(name: &str, contents: &str, width: Option<usize>) -> Result<usize, String> {
+        let path = std::env::temp_dir().join(format!("day3-{}-{}", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let report = read_report(&path, width);
+        std::fs::remove_file(&path).unwrap();
+        report.map(|(lines, width)| lines.len() * width).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_read_report() {
+        assert_eq!(read("good", "101\n010\n", None), Ok(6));
+        assert_eq!(read("declared", "101\n010\n", Some(3)), Ok(6));
+        assert_eq!(
+            read("empty", "", None),
+            Err(String::from("Error for command: Diagnostic report is empty"))
+        );
+        assert_eq!(
+            read("mixed", "101\n01\n", None),
+            Err(String::from("Error for command: line 2 has width 2, expected 3"))
+        );
+        assert_eq!(
+            read("mismatch", "101\n010\n", Some(4)),
+            Err(String::from("Error for command: line 1 has width 3, expected 4"))
+        );
+        assert_eq!(
+            read("digit", "101\n0201\n", None),
+            Err(String::from(
+                "Error at line 2: Error for command: Invalid binary digit '2' at column 2"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_wide_lines() {
+        let raw = format!("1{}1{}", "0".repeat(70), "0".repeat(130));
+        let line = raw.parse::<DiagnosticReportLine>().unwrap();
+        assert_eq!(line.width(), 202);
+        assert_eq!(line.ones().collect::<Vec<_>>(), vec![0, 71]);
+        assert_eq!(line.to_string(), raw);
+        assert_eq!(line.to_biguint(), BigUint::parse_bytes(raw.as_bytes(), 2).unwrap());
+    }
+
+    fn report() -> impl Strategy<Value = Vec<String>> {
+        (1..130usize).prop_flat_map(|width| {
+            prop::collection::vec(prop::collection::vec(prop::bool::ANY, width), 1..80).prop_map(
+                |lines| {
+                    lines
+                        .iter()
+                        .map(|bits| bits.iter().map(|&b| if b { '1' } else { '0' }).collect())
+                        .collect()
+                },
+            )
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_gamma_epsilon(raw in report()) {
+            let lines: Vec<DiagnosticReportLine> = raw.iter().map(|l| l.parse().unwrap()).collect();
+            let width = lines[0].width();
+            let counts = ColumnCounts::from_lines(&lines, width);
+            prop_assert_eq!(&BitSlicedReport::new(&lines, width).counts(), &counts);
+
+            // Epsilon is gamma with every bit flipped
+            let (gamma, epsilon) = gamma_epsilon(&counts);
+            let all_ones = (BigUint::from(1u8) << width) - 1u8;
+            prop_assert_eq!(gamma.to_biguint() ^ epsilon.to_biguint(), all_ones);
+
+            // Gamma agrees with counting characters column by column
+            let oracle: String = (0..width)
+                .map(|i| {
+                    let ones = raw.iter().filter(|l| l.as_bytes()[i] == b'1').count();
+                    if ones * 2 > raw.len() { '1' } else { '0' }
+                })
+                .collect();
+            prop_assert_eq!(gamma.to_string(), oracle);
+        }
     }
 }
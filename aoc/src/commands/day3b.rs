@@ -1,137 +1,406 @@
-use std::{error::Error, fmt, path::PathBuf, str::FromStr};
+use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{ArgEnum, Parser};
 
-use crate::utils::slurp_file;
-
-use super::{CommandImpl, DynError};
+use super::{
+    day3a::{read_report, BitSlicedReport, DiagnosticReportLine},
+    CommandImpl, DynError,
+};
 
 #[derive(Parser, Debug)]
 pub struct Day3b {
     #[clap(long, short)]
     input: PathBuf,
+    /// Expected width of every line, defaults to the width of the first line
+    #[clap(long, short)]
+    width: Option<usize>,
+    /// Bit kept by the oxygen rating when a column is tied
+    #[clap(long, arg_enum, default_value = "prefer-one")]
+    oxygen_tie_break: TieBreak,
+    /// Bit kept by the CO2 rating when a column is tied
+    #[clap(long, arg_enum, default_value = "prefer-zero")]
+    co2_tie_break: TieBreak,
+    /// Order the columns are considered in
+    #[clap(long, arg_enum, default_value = "msb-first")]
+    order: BitOrder,
+    /// Print the candidates left after each column, only used by the filter algorithm
+    #[clap(long)]
+    explain: bool,
+    #[clap(long, arg_enum, default_value = "filter")]
+    algorithm: Algorithm,
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    /// Filter a bit-sliced copy of the report one column at a time
+    Filter,
+    /// Build a trie of the report once and walk it for each rating
+    Trie,
 }
 
 impl CommandImpl for Day3b {
     fn main(&self) -> Result<(), DynError> {
-        let diagnostics: Vec<DiagnosticReportLine> = slurp_file(&self.input)?;
-
-        let co2_mask = u64::MAX << diagnostics[0].width;
-        let mut oxygen: Option<u64> = None;
-        let mut co2: Option<u64> = None;
-        let mut mask = !(u64::MAX >> 1);
-
-        let mut oxygen_possible_values = diagnostics.clone();
-        let mut co2_possible_values = diagnostics.clone();
-
-        for _ in 0..u64::BITS {
-            if oxygen.is_none() {
-                let most_common = count_zeros_and_ones(&oxygen_possible_values, mask);
-                oxygen_possible_values = oxygen_possible_values
-                    .into_iter()
-                    .filter(|DiagnosticReportLine { value, width: _ }| {
-                        most_common.is_most_common(mask & value)
-                    })
-                    .collect();
-                if oxygen_possible_values.len() == 1 {
-                    oxygen = Some(oxygen_possible_values[0].value);
-                }
-            }
+        let (diagnostics, width) = read_report(&self.input, self.width)?;
+        let oxygen_criteria = BitCriteria {
+            criterion: Criterion::MostCommon,
+            tie_break: self.oxygen_tie_break,
+            order: self.order,
+        };
+        let co2_criteria = BitCriteria {
+            criterion: Criterion::LeastCommon,
+            tie_break: self.co2_tie_break,
+            order: self.order,
+        };
 
-            if co2.is_none() {
-                let most_common = count_zeros_and_ones(&co2_possible_values, mask);
-                co2_possible_values = co2_possible_values
-                    .into_iter()
-                    .filter(|DiagnosticReportLine { value, width: _ }| {
-                        // Skip over all values that fall outside the width
-                        ((co2_mask & mask) > 0) || most_common.is_least_common(mask & value)
-                    })
-                    .collect();
-                if co2_possible_values.len() == 1 {
-                    co2 = Some(co2_possible_values[0].value);
+        let (oxygen, co2) = match self.algorithm {
+            Algorithm::Filter => {
+                let report = BitSlicedReport::new(&diagnostics, width);
+                let oxygen = oxygen_criteria.rate(&diagnostics, &report);
+                let co2 = co2_criteria.rate(&diagnostics, &report);
+                if self.explain {
+                    println!("Oxygen:\n{}", oxygen.explain(&diagnostics));
+                    println!("CO2:\n{}", co2.explain(&diagnostics));
                 }
+                (oxygen.value, co2.value)
             }
-            if oxygen.is_some() && co2.is_some() {
-                break;
+            Algorithm::Trie => {
+                let trie = RatingTrie::new(&diagnostics, self.order);
+                (trie.rate(&oxygen_criteria), trie.rate(&co2_criteria))
             }
+        };
 
-            mask = mask.rotate_right(1);
-        }
-
-        println!("Oxygen: {}", oxygen.unwrap());
-        println!("CO2: {}", co2.unwrap());
-        println!("Answer: {}", oxygen.unwrap() * co2.unwrap());
+        let oxygen = oxygen.ok_or("No single line is left for the oxygen generator rating")?;
+        let co2 = co2.ok_or("No single line is left for the CO2 scrubber rating")?;
+        println!("Oxygen: {} ({})", oxygen.to_biguint(), oxygen);
+        println!("CO2: {} ({})", co2.to_biguint(), co2);
+        println!("Answer: {}", oxygen.to_biguint() * co2.to_biguint());
 
         Ok(())
     }
 }
 
-#[derive(Debug)]
-pub enum MostCommon {
-    One,
-    Zero,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Criterion {
+    /// Keep lines with the most common bit in the column
+    MostCommon,
+    /// Keep lines with the least common bit in the column
+    LeastCommon,
 }
 
-impl MostCommon {
-    fn is_most_common(&self, value: u64) -> bool {
-        match self {
-            MostCommon::One => value > 0,
-            MostCommon::Zero => value == 0,
-        }
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum TieBreak {
+    /// Keep lines with a zero when a column is tied
+    PreferZero,
+    /// Keep lines with a one when a column is tied
+    PreferOne,
+    /// Keep every line when a column is tied
+    Drop,
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+/// Rules for filtering the report down to a single rating
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitCriteria {
+    pub criterion: Criterion,
+    pub tie_break: TieBreak,
+    pub order: BitOrder,
+}
+
+/// The outcome of filtering one column
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatingStep {
+    pub column: usize,
+    pub ones: usize,
+    pub zeros: usize,
+    /// The bit kept, `None` if the column was dropped on a tie
+    pub kept: Option<bool>,
+    /// Mask of the lines left after this step, one bit per line
+    pub candidates: Vec<u64>,
+}
+
+impl RatingStep {
+    /// Index of each line left after this step
+    pub fn candidates(&self) -> impl Iterator<Item = usize> + '_ {
+        mask_lines(&self.candidates)
     }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rating {
+    /// The rating, `None` if no single line satisfied the criteria
+    pub value: Option<DiagnosticReportLine>,
+    pub trace: Vec<RatingStep>,
+}
 
-    fn is_least_common(&self, value: u64) -> bool {
-        match self {
-            MostCommon::One => value == 0,
-            MostCommon::Zero => value > 0,
+impl Rating {
+    /// Describe each step of the trace, listing the candidates when there are only a few
+    pub fn explain(&self, lines: &[DiagnosticReportLine]) -> String {
+        let mut out = String::new();
+        for step in &self.trace {
+            let kept = match step.kept {
+                Some(true) => "keep 1",
+                Some(false) => "keep 0",
+                None => "tied, keep all",
+            };
+            let left: Vec<usize> = step.candidates().collect();
+            out.push_str(&format!(
+                "  column {}: {} ones, {} zeros, {} -> {} candidates",
+                step.column,
+                step.ones,
+                step.zeros,
+                kept,
+                left.len()
+            ));
+            if left.len() <= 8 {
+                let left: Vec<String> = left.iter().map(|&i| lines[i].to_string()).collect();
+                out.push_str(&format!(" [{}]", left.join(", ")));
+            }
+            out.push('\n');
         }
+        out
     }
 }
 
-fn count_zeros_and_ones(diagnostics: &[DiagnosticReportLine], mask: u64) -> MostCommon {
-    let mut ones = 0;
-    let mut zeros = 0;
-    for DiagnosticReportLine { value, width: _ } in diagnostics {
-        if (value & mask) > 0 {
-            ones += 1;
-        } else {
-            zeros += 1;
+impl BitCriteria {
+    /// Filter `lines` one column at a time until a single line is left.
+    ///
+    /// `report` must be the bit-sliced form of `lines`.
+    pub fn rate(&self, lines: &[DiagnosticReportLine], report: &BitSlicedReport) -> Rating {
+        let mut candidates = report.all_lines();
+        let mut left = lines.len();
+        let mut trace = vec![];
+        let columns: Vec<usize> = match self.order {
+            BitOrder::MsbFirst => (0..report.width()).collect(),
+            BitOrder::LsbFirst => (0..report.width()).rev().collect(),
+        };
+
+        for column in columns {
+            if left <= 1 {
+                break;
+            }
+            let bits = report.column(column);
+            let ones: usize =
+                bits.iter().zip(&candidates).map(|(b, c)| (b & c).count_ones() as usize).sum();
+            let zeros = left - ones;
+
+            let kept = self.keep(ones, zeros);
+            if let Some(keep_ones) = kept {
+                for (c, b) in candidates.iter_mut().zip(bits) {
+                    *c &= if keep_ones { *b } else { !*b };
+                }
+                left = if keep_ones { ones } else { zeros };
+            }
+            trace.push(RatingStep { column, ones, zeros, kept, candidates: candidates.clone() });
         }
+
+        // Duplicate lines can survive every column, they still make a single rating
+        let mut remaining = mask_lines(&candidates).map(|i| &lines[i]).collect::<Vec<_>>();
+        remaining.dedup();
+        let value = if remaining.len() == 1 { Some(remaining[0].clone()) } else { None };
+        Rating { value, trace }
     }
 
-    if ones >= zeros {
-        MostCommon::One
-    } else {
-        MostCommon::Zero
+    /// The bit to keep given the counts in a column. A bit no candidate has is never kept, as
+    /// that would leave no candidates at all.
+    pub fn keep(&self, ones: usize, zeros: usize) -> Option<bool> {
+        if ones == zeros {
+            return match self.tie_break {
+                TieBreak::PreferZero => Some(false),
+                TieBreak::PreferOne => Some(true),
+                TieBreak::Drop => None,
+            };
+        }
+        if ones == 0 || zeros == 0 {
+            return Some(zeros == 0);
+        }
+        match self.criterion {
+            Criterion::MostCommon => Some(ones > zeros),
+            Criterion::LeastCommon => Some(ones < zeros),
+        }
     }
 }
 
+/// A binary trie of the report with the number of lines below each node.
+///
+/// Each level of the trie is one column, taken in `order`, so a rating is a walk from the root
+/// choosing a child by its count at each level rather than a pass over the remaining lines.
 #[derive(Debug, Clone)]
-pub struct DiagnosticError {
-    msg: String,
+pub struct RatingTrie {
+    nodes: Vec<TrieNode>,
+    order: BitOrder,
+    width: usize,
 }
 
-impl Error for DiagnosticError {}
+#[derive(Debug, Clone, Copy, Default)]
+struct TrieNode {
+    /// Index of the zero and one child, 0 for no child as the root is never a child
+    children: [usize; 2],
+    count: usize,
+}
+
+impl RatingTrie {
+    pub fn new(lines: &[DiagnosticReportLine], order: BitOrder) -> Self {
+        let width = lines.first().map_or(0, |l| l.width());
+        let mut trie = Self { nodes: vec![TrieNode::default()], order, width };
+        for line in lines {
+            let mut node = 0;
+            trie.nodes[node].count += 1;
+            for depth in 0..width {
+                let bit = line.bit(trie.column(depth)) as usize;
+                if trie.nodes[node].children[bit] == 0 {
+                    trie.nodes[node].children[bit] = trie.nodes.len();
+                    trie.nodes.push(TrieNode::default());
+                }
+                node = trie.nodes[node].children[bit];
+                trie.nodes[node].count += 1;
+            }
+        }
+        trie
+    }
+
+    /// The column checked at `depth` of the trie
+    fn column(&self, depth: usize) -> usize {
+        match self.order {
+            BitOrder::MsbFirst => depth,
+            BitOrder::LsbFirst => self.width - 1 - depth,
+        }
+    }
+
+    fn child_count(&self, node: usize, bit: usize) -> usize {
+        match self.nodes[node].children[bit] {
+            0 => 0,
+            child => self.nodes[child].count,
+        }
+    }
+
+    /// Walk the trie following `criteria`, the bit order of the criteria is ignored in favor of
+    /// the order the trie was built with.
+    pub fn rate(&self, criteria: &BitCriteria) -> Option<DiagnosticReportLine> {
+        // Every node still holding candidates along with the bits of the path to it.
+        // Only a tie with `TieBreak::Drop` keeps more than one node.
+        let mut frontier = vec![(0, DiagnosticReportLine::zeros(self.width))];
+        for depth in 0..self.width {
+            let count = |bit: usize| -> usize {
+                frontier.iter().map(|&(node, _)| self.child_count(node, bit)).sum()
+            };
+            let (zeros, ones) = (count(0), count(1));
+            let bits: &[usize] = match (zeros + ones, criteria.keep(ones, zeros)) {
+                // A single candidate left, follow it to the end
+                (1, _) if zeros == 1 => &[0],
+                (1, _) => &[1],
+                (_, Some(false)) => &[0],
+                (_, Some(true)) => &[1],
+                (_, None) => &[0, 1],
+            };
+
+            let mut next = vec![];
+            for (node, path) in &frontier {
+                for &bit in bits {
+                    let child = self.nodes[*node].children[bit];
+                    if child != 0 {
+                        let mut path = path.clone();
+                        if bit == 1 {
+                            path.set_bit(self.column(depth));
+                        }
+                        next.push((child, path));
+                    }
+                }
+            }
+            frontier = next;
+        }
 
-impl fmt::Display for DiagnosticError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Error for command: {}", self.msg)
+        match frontier.as_slice() {
+            [(node, path)] if self.nodes[*node].count > 0 => Some(path.clone()),
+            _ => None,
+        }
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct DiagnosticReportLine {
-    value: u64,
-    width: u64,
+/// Index of each line set in a mask holding one bit per line
+fn mask_lines(mask: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    mask.iter().enumerate().flat_map(|(w, &word)| {
+        (0..64).filter(move |b| word & (1 << b) != 0).map(move |b| w * 64 + b)
+    })
 }
 
-impl FromStr for DiagnosticReportLine {
-    type Err = DiagnosticError;
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rate(lines: &[&str], criterion: Criterion, tie_break: TieBreak, order: BitOrder) -> Rating {
+        let lines: Vec<DiagnosticReportLine> = lines.iter().map(|l| l.parse().unwrap()).collect();
+        let report = BitSlicedReport::new(&lines, lines[0].width());
+        BitCriteria { criterion, tie_break, order }.rate(&lines, &report)
+    }
+
+    #[test]
+    fn test_example_ratings() {
+        let example = [
+            "00100", "11110", "10110", "10111", "10101", "01111", "00111", "11100", "10000",
+            "11001", "00010", "01010",
+        ];
+        let msb = BitOrder::MsbFirst;
+        let oxygen = rate(&example, Criterion::MostCommon, TieBreak::PreferOne, msb);
+        assert_eq!(oxygen.value.unwrap().to_string(), "10111");
+        let left: Vec<usize> = oxygen.trace.iter().map(|s| s.candidates().count()).collect();
+        assert_eq!(left, vec![7, 4, 3, 2, 1]);
+
+        let co2 = rate(&example, Criterion::LeastCommon, TieBreak::PreferZero, msb);
+        assert_eq!(co2.value.unwrap().to_string(), "01010");
+        assert_eq!(co2.trace.len(), 3);
+    }
+
+    #[test]
+    fn test_trie_matches_filter() {
+        let lines = [
+            "00100", "11110", "10110", "10111", "10101", "01111", "00111", "11100", "10000",
+            "11001", "00010", "01010", "01010",
+        ];
+        let parsed: Vec<DiagnosticReportLine> = lines.iter().map(|l| l.parse().unwrap()).collect();
+        for &order in &[BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            let trie = RatingTrie::new(&parsed, order);
+            for &criterion in &[Criterion::MostCommon, Criterion::LeastCommon] {
+                for &tie_break in &[TieBreak::PreferZero, TieBreak::PreferOne, TieBreak::Drop] {
+                    let criteria = BitCriteria { criterion, tie_break, order };
+                    let filtered = rate(&lines, criterion, tie_break, order).value;
+                    assert_eq!(trie.rate(&criteria), filtered, "{:?}", criteria);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tie_breaks() {
+        let lines = ["10", "01"];
+        let lsb = BitOrder::LsbFirst;
+        let zero = rate(&lines, Criterion::MostCommon, TieBreak::PreferZero, lsb);
+        assert_eq!(zero.value.unwrap().to_string(), "10");
+
+        let dropped = rate(&lines, Criterion::MostCommon, TieBreak::Drop, lsb);
+        assert_eq!(dropped.value, None);
+        assert!(dropped.trace.iter().all(|s| s.kept.is_none()));
+    }
+
+    #[test]
+    fn test_shared_bit_is_kept() {
+        // Every line has a zero in the first column, so even the least common criterion keeps it
+        let lines = ["001", "010", "011"];
+        let co2 = rate(&lines, Criterion::LeastCommon, TieBreak::PreferZero, BitOrder::MsbFirst);
+        assert_eq!(co2.trace[0].kept, Some(false));
+        assert_eq!(co2.value.unwrap().to_string(), "001");
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let width = s.len() as u64;
-        let value =
-            u64::from_str_radix(s, 2).map_err(|e| DiagnosticError { msg: e.to_string() })?;
-        Ok(DiagnosticReportLine { value, width })
+        let parsed: Vec<DiagnosticReportLine> = lines.iter().map(|l| l.parse().unwrap()).collect();
+        let trie = RatingTrie::new(&parsed, BitOrder::MsbFirst);
+        let criteria = BitCriteria {
+            criterion: Criterion::LeastCommon,
+            tie_break: TieBreak::PreferZero,
+            order: BitOrder::MsbFirst,
+        };
+        assert_eq!(trie.rate(&criteria).unwrap().to_string(), "001");
     }
 }
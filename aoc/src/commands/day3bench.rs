@@ -0,0 +1,71 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+
+use super::{
+    day3a::{read_report, BitSlicedReport, DiagnosticReportLine},
+    day3b::{BitCriteria, BitOrder, Criterion, RatingTrie, TieBreak},
+    CommandImpl, DynError,
+};
+
+/// Time the filter and trie algorithms for the Day 3 ratings against each other
+#[derive(Parser, Debug)]
+pub struct Day3Bench {
+    #[clap(long, short)]
+    input: PathBuf,
+    /// Number of times to run each algorithm
+    #[clap(long, short = 'n', default_value = "10")]
+    iterations: u32,
+}
+
+type Ratings = (Option<DiagnosticReportLine>, Option<DiagnosticReportLine>);
+
+impl CommandImpl for Day3Bench {
+    fn main(&self) -> Result<(), DynError> {
+        let (diagnostics, width) = read_report(&self.input, None)?;
+        let oxygen = BitCriteria {
+            criterion: Criterion::MostCommon,
+            tie_break: TieBreak::PreferOne,
+            order: BitOrder::MsbFirst,
+        };
+        let co2 = BitCriteria {
+            criterion: Criterion::LeastCommon,
+            tie_break: TieBreak::PreferZero,
+            order: BitOrder::MsbFirst,
+        };
+
+        let (filter_time, filtered) = self.time(|| {
+            let report = BitSlicedReport::new(&diagnostics, width);
+            (oxygen.rate(&diagnostics, &report).value, co2.rate(&diagnostics, &report).value)
+        });
+        let (trie_time, walked) = self.time(|| {
+            let trie = RatingTrie::new(&diagnostics, BitOrder::MsbFirst);
+            (trie.rate(&oxygen), trie.rate(&co2))
+        });
+        if filtered != walked {
+            return Err(
+                format!("Algorithms disagree: filter={:?} trie={:?}", filtered, walked).into()
+            );
+        }
+
+        println!("Lines: {}, width: {}", diagnostics.len(), width);
+        println!("filter: {:?} per run", filter_time);
+        println!("trie:   {:?} per run", trie_time);
+        Ok(())
+    }
+}
+
+impl Day3Bench {
+    /// Mean time of `run` over all iterations along with its last result
+    fn time(&self, run: impl Fn() -> Ratings) -> (Duration, Ratings) {
+        let start = Instant::now();
+        let mut result = run();
+        for _ in 1..self.iterations {
+            result = run();
+        }
+        (start.elapsed() / self.iterations.max(1), result)
+    }
+}
@@ -1,13 +1,15 @@
 use std::{
+    collections::{HashMap, HashSet},
     error::Error,
     fmt,
-    fs::File,
-    io::{BufRead, BufReader},
+    io::BufRead,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-use clap::Parser;
+use clap::{ArgEnum, Parser};
+
+use crate::utils::{open_file, read_to_string};
 
 use super::{CommandImpl, DynError};
 
@@ -15,56 +17,326 @@ use super::{CommandImpl, DynError};
 pub struct Day4a {
     #[clap(long, short)]
     input: PathBuf,
+    /// Which winners to report
+    #[clap(long, arg_enum, default_value = "first")]
+    rank: Rank,
+    #[clap(flatten)]
+    win: WinOpts,
+    /// Warn about malformed boards instead of failing
+    #[clap(long)]
+    lenient: bool,
 }
 
 impl CommandImpl for Day4a {
     fn main(&self) -> Result<(), DynError> {
-        let (numbers, mut boards) = read_file(&self.input)?;
-
-        'bingo: for number in numbers.0 {
-            for board in boards.iter_mut() {
-                board.set_value(number);
-                if board.has_won() {
-                    let score = board.score_board(number);
-                    println!("{}", board);
-                    println!("Winner: {}", score);
-                    break 'bingo;
+        let (numbers, mut boards) = read_file(&self.input, self.lenient)?;
+        self.win.apply(&mut boards)?;
+        play_and_report(&numbers, boards, self.rank);
+        Ok(())
+    }
+}
+
+/// Options selecting which lines win a board
+#[derive(Parser, Debug)]
+pub struct WinOpts {
+    /// Comma separated lines that win a board, rows and columns unless a `--pattern` is given
+    #[clap(long, arg_enum, multiple_occurrences = true, use_delimiter = true)]
+    win: Vec<WinKind>,
+    /// File holding a winning mask, `X` marks a cell in the pattern and `.` any other cell
+    #[clap(long, multiple_occurrences = true)]
+    pattern: Vec<PathBuf>,
+}
+
+impl WinOpts {
+    pub fn rules(&self) -> Result<Vec<WinRule>, DynError> {
+        let mut rules: Vec<WinRule> = self.win.iter().map(|&kind| kind.into()).collect();
+        if rules.is_empty() && self.pattern.is_empty() {
+            rules = vec![WinRule::Rows, WinRule::Columns];
+        }
+        for path in &self.pattern {
+            rules.push(WinRule::Pattern(read_to_string(path)?.parse()?));
+        }
+        Ok(rules)
+    }
+
+    /// Set the selected win rules on every board
+    pub fn apply(&self, boards: &mut [BingoBoard]) -> Result<(), DynError> {
+        let rules = self.rules()?;
+        for board in boards.iter_mut() {
+            board.set_rules(&rules)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum WinKind {
+    Rows,
+    Columns,
+    Diagonals,
+    Corners,
+    Blackout,
+}
+
+impl From<WinKind> for WinRule {
+    fn from(kind: WinKind) -> Self {
+        match kind {
+            WinKind::Rows => WinRule::Rows,
+            WinKind::Columns => WinRule::Columns,
+            WinKind::Diagonals => WinRule::Diagonals,
+            WinKind::Corners => WinRule::Corners,
+            WinKind::Blackout => WinRule::Blackout,
+        }
+    }
+}
+
+/// A set of lines that each win the board when fully marked
+#[derive(Debug, Clone, PartialEq)]
+pub enum WinRule {
+    /// Any full row
+    Rows,
+    /// Any full column
+    Columns,
+    /// Either of the two main diagonals
+    Diagonals,
+    /// The four corner cells
+    Corners,
+    /// Every cell on the board
+    Blackout,
+    /// Every cell set in the mask
+    Pattern(Pattern),
+}
+
+/// A mask of cells, parsed from lines of `X` (in the pattern) and `.` (not in the pattern)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    cells: Vec<(usize, usize)>,
+}
+
+impl FromStr for Pattern {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Blank lines around the pattern are ignored, but inside it they would shift later rows
+        let lines: Vec<&str> = s.lines().collect();
+        let blank = |l: &&str| l.trim().is_empty();
+        let first = lines.iter().position(|l| !blank(l)).unwrap_or(lines.len());
+        let last = lines.iter().rposition(|l| !blank(l)).map_or(first, |i| i + 1);
+        let mut cells = vec![];
+        for (r, line) in lines[first..last].iter().enumerate() {
+            let line_number = first + r + 1;
+            if blank(line) {
+                return Err(ParseError {
+                    msg: format!("Blank line {} inside the pattern", line_number),
+                });
+            }
+            for (c, ch) in line.split_ascii_whitespace().collect::<String>().chars().enumerate() {
+                match ch {
+                    'X' | 'x' | '#' => cells.push((r, c)),
+                    '.' => (),
+                    _ => {
+                        return Err(ParseError {
+                            msg: format!(
+                                "Invalid pattern character {:?} at line {}",
+                                ch, line_number
+                            ),
+                        })
+                    }
                 }
             }
         }
+        if cells.is_empty() {
+            return Err(ParseError { msg: String::from("Pattern has no cells set") });
+        }
+        Ok(Pattern { cells })
+    }
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Rank {
+    First,
+    Last,
+    All,
+}
+
+/// Play every draw and print the boards selected by `rank`.
+pub fn play_and_report(numbers: &Numbers, boards: Vec<BingoBoard>, rank: Rank) {
+    let mut hall = BingoHall::new(boards.clone());
+    let results = hall.play(&numbers.0);
+
+    let result = match rank {
+        Rank::First => results.first(),
+        Rank::Last => results.last(),
+        Rank::All => {
+            results.iter().for_each(|result| println!("{}", result));
+            let mut won = vec![false; boards.len()];
+            results.iter().for_each(|r| won[r.board] = true);
+            for board in (0..boards.len()).filter(|&i| !won[i]) {
+                println!("Board {} never won", board);
+            }
+            return;
+        }
+    };
 
-        Ok(())
+    if let Some(result) = result {
+        // Replay up to the winning draw so the board shows its marks at the time it won
+        let mut hall = BingoHall::new(boards);
+        for &number in &numbers.0[..=result.draw] {
+            hall.call(number);
+        }
+        print!("{}", hall.boards[result.board].render(atty::is(atty::Stream::Stdout)));
+        println!("{}", result);
+        println!("Winner: {}", result.score);
+    } else {
+        println!("No board won");
     }
 }
 
-fn read_file(path: impl AsRef<Path>) -> Result<(Numbers, Vec<BingoBoard>), DynError> {
-    let reader = File::open(path).map(BufReader::new)?;
+/// Read the called numbers and boards, validating each board.
+///
+/// With `lenient` set validation problems are printed as warnings instead of returned as errors.
+pub fn read_file(
+    path: impl AsRef<Path>,
+    lenient: bool,
+) -> Result<(Numbers, Vec<BingoBoard>), DynError> {
+    let reader = open_file(path)?;
     let mut lines = reader.lines();
 
-    let numbers = lines.next().expect("Missing first line of file")?.parse::<Numbers>()?;
-    let mut boards = vec![];
+    let numbers = lines
+        .next()
+        .ok_or(ParseError { msg: String::from("Missing first line of file") })??
+        .parse::<Numbers>()
+        .map_err(|e| ParseError { msg: format!("line 1: {}", e.msg) })?;
 
+    // The rows of each board along with the line number of each row
+    let mut raw_boards: Vec<Vec<(usize, Vec<usize>)>> = vec![];
     let mut temp = vec![];
-    for line in lines {
+    for (i, line) in lines.enumerate() {
+        let line_number = i + 2;
         let line = line?;
-        if line.is_empty() {
+        if line.trim().is_empty() {
             if !temp.is_empty() {
-                boards.push(temp.join("\n").parse::<BingoBoard>()?);
+                raw_boards.push(temp);
+                temp = vec![];
             }
-            temp.clear();
             continue;
         }
-        temp.push(line);
+        let row = parse_row(&line).map_err(|e| BoardError {
+            board: raw_boards.len(),
+            line: line_number,
+            msg: e.msg,
+        })?;
+        if temp.len() == MAX_SIDE {
+            let msg = too_many_rows().msg;
+            return Err(BoardError { board: raw_boards.len(), line: line_number, msg }.into());
+        }
+        temp.push((line_number, row));
     }
     if !temp.is_empty() {
-        boards.push(temp.join("\n").parse::<BingoBoard>()?);
+        raw_boards.push(temp);
     }
+    if raw_boards.is_empty() {
+        return Err(ParseError { msg: String::from("No boards found") }.into());
+    }
+
+    for issue in validate(&numbers, &raw_boards) {
+        if lenient {
+            eprintln!("Warning: {}", issue);
+        } else {
+            return Err(issue.into());
+        }
+    }
+
+    let boards = raw_boards
+        .into_iter()
+        .map(|rows| {
+            BingoBoard::new(
+                rows.into_iter()
+                    .map(|(_, row)| row.into_iter().map(BCell::new).collect())
+                    .collect(),
+            )
+        })
+        .collect();
     Ok((numbers, boards))
 }
 
+/// Check every board is square, the same size as the first board, holds no duplicates, and
+/// only holds numbers that are drawn.
+fn validate(numbers: &Numbers, boards: &[Vec<(usize, Vec<usize>)>]) -> Vec<BoardError> {
+    let drawn: HashSet<usize> = numbers.0.iter().copied().collect();
+    let expected_size = boards[0].len();
+    let mut issues = vec![];
+    for (board, rows) in boards.iter().enumerate() {
+        let start = rows[0].0;
+        let issue = |line, msg| BoardError { board, line, msg };
+
+        if rows.len() != expected_size {
+            let msg = format!("has {} rows, expected {}", rows.len(), expected_size);
+            issues.push(issue(start, msg));
+        }
+        let mut seen = HashSet::new();
+        for (line, row) in rows {
+            if row.len() != rows.len() {
+                let msg = format!("row has {} values, expected {}", row.len(), rows.len());
+                issues.push(issue(*line, msg));
+            }
+            for value in row {
+                if !seen.insert(value) {
+                    issues.push(issue(*line, format!("value {} appears more than once", value)));
+                }
+                if !drawn.contains(value) {
+                    issues.push(issue(*line, format!("value {} is never drawn", value)));
+                }
+            }
+        }
+    }
+    issues
+}
+
+/// Largest number accepted on a board or in the draws, the puzzle itself stays below 100
+pub const MAX_VALUE: usize = 99_999;
+/// Most rows in a board and values in a row, so a score always fits in 64 bits
+pub const MAX_SIDE: usize = 1000;
+
+fn parse_value(v: &str) -> Result<usize, ParseError> {
+    let value = v.parse::<usize>().map_err(|e| ParseError { msg: format!("{:?}: {}", v, e) })?;
+    if value > MAX_VALUE {
+        return Err(ParseError { msg: format!("{} is above {}", value, MAX_VALUE) });
+    }
+    Ok(value)
+}
+
+fn parse_row(s: &str) -> Result<Vec<usize>, ParseError> {
+    let row = s.split_ascii_whitespace().map(parse_value).collect::<Result<Vec<_>, _>>()?;
+    if row.len() > MAX_SIDE {
+        return Err(ParseError { msg: format!("row has more than {} values", MAX_SIDE) });
+    }
+    Ok(row)
+}
+
+fn too_many_rows() -> ParseError {
+    ParseError { msg: format!("board has more than {} rows", MAX_SIDE) }
+}
+
+/// A problem with a single board, `board` is zero based and `line` is one based
+#[derive(Debug, Clone)]
+pub struct BoardError {
+    board: usize,
+    line: usize,
+    msg: String,
+}
+
+impl Error for BoardError {}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Board {} at line {}: {}", self.board, self.line, self.msg)
+    }
+}
+
 // TODO: make a simple macro for making these error types
 #[derive(Debug, Clone)]
-struct ParseError {
+pub struct ParseError {
     msg: String,
 }
 
@@ -78,17 +350,13 @@ impl fmt::Display for ParseError {
 
 /// The called bingo numbers
 #[derive(Debug, Clone)]
-struct Numbers(Vec<usize>);
+pub struct Numbers(pub Vec<usize>);
 
 impl FromStr for Numbers {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut values = vec![];
-        for v in s.split(',') {
-            values.push(v.parse::<usize>().map_err(|e| ParseError { msg: e.to_string() })?);
-        }
-        Ok(Numbers(values))
+        s.split(',').map(parse_value).collect::<Result<_, _>>().map(Numbers)
     }
 }
 
@@ -108,50 +376,197 @@ impl BCell {
 }
 
 #[derive(Debug, Clone)]
-struct BingoBoard {
+pub struct BingoBoard {
     rows: Vec<Vec<BCell>>,
+    /// Value -> (row, col) of every cell holding that value
+    index: HashMap<usize, Vec<(usize, usize)>>,
+    /// Every line that wins the board when fully marked
+    lines: Vec<WinLine>,
+    /// (row, col) -> index into `lines` of every line holding that cell
+    cell_lines: Vec<Vec<Vec<usize>>>,
+    /// Index into `lines` of the first line completed
+    won: Option<usize>,
 }
 
+/// Renders the board as a grid with marked cells in `[]` and the winning line in `<>`
 impl fmt::Display for BingoBoard {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for row in &self.rows {
-            writeln!(f, "{:?}", row)?;
-        }
-        Ok(())
+        write!(f, "{}", self.render(false))
     }
 }
 
 impl BingoBoard {
-    fn has_won(&self) -> bool {
-        // Check row-wise win con
-        for row in &self.rows {
-            if row.iter().all(|cell| cell.marked) {
-                return true;
+    fn new(rows: Vec<Vec<BCell>>) -> Self {
+        let mut index: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        for (r, row) in rows.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                index.entry(cell.value).or_default().push((r, c));
+            }
+        }
+        let cell_lines = rows.iter().map(|r| vec![vec![]; r.len()]).collect();
+        let mut board = Self { rows, index, lines: vec![], cell_lines, won: None };
+        board
+            .set_rules(&[WinRule::Rows, WinRule::Columns])
+            .expect("Rows and columns fit any board");
+        board
+    }
+
+    /// Replace the lines that win this board, marks made so far are kept.
+    pub fn set_rules(&mut self, rules: &[WinRule]) -> Result<(), ParseError> {
+        let height = self.rows.len();
+        let width = self.rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        let mut lines: Vec<(WinningLine, Vec<(usize, usize)>)> = vec![];
+        for rule in rules {
+            match rule {
+                WinRule::Rows => {
+                    for (r, row) in self.rows.iter().enumerate() {
+                        lines.push((WinningLine::Row(r), (0..row.len()).map(|c| (r, c)).collect()));
+                    }
+                }
+                WinRule::Columns => {
+                    for c in 0..width {
+                        let cells = (0..height).filter(|&r| c < self.rows[r].len());
+                        lines.push((WinningLine::Column(c), cells.map(|r| (r, c)).collect()));
+                    }
+                }
+                WinRule::Diagonals => {
+                    // Only the leading square of a malformed board is considered
+                    let size = self.rows.iter().map(|r| r.len()).min().unwrap_or(0).min(height);
+                    lines.push((WinningLine::Diagonal, (0..size).map(|i| (i, i)).collect()));
+                    let anti = (0..size).map(|i| (i, size - 1 - i)).collect();
+                    lines.push((WinningLine::AntiDiagonal, anti));
+                }
+                WinRule::Corners => {
+                    let mut corners = vec![];
+                    for r in [0, height.saturating_sub(1)] {
+                        let row_width = self.rows.get(r).map_or(0, |row| row.len());
+                        for c in [0, row_width.saturating_sub(1)] {
+                            if c < row_width && !corners.contains(&(r, c)) {
+                                corners.push((r, c));
+                            }
+                        }
+                    }
+                    lines.push((WinningLine::Corners, corners));
+                }
+                WinRule::Blackout => {
+                    let cells = self.rows.iter().enumerate();
+                    let cells = cells.flat_map(|(r, row)| (0..row.len()).map(move |c| (r, c)));
+                    lines.push((WinningLine::Blackout, cells.collect()));
+                }
+                WinRule::Pattern(pattern) => {
+                    let patterns =
+                        lines.iter().filter(|(l, _)| matches!(l, WinningLine::Pattern(_)));
+                    let kind = WinningLine::Pattern(patterns.count());
+                    for &(r, c) in &pattern.cells {
+                        if self.rows.get(r).map_or(true, |row| c >= row.len()) {
+                            return Err(ParseError {
+                                msg: format!("Pattern cell ({}, {}) is outside the board", r, c),
+                            });
+                        }
+                    }
+                    lines.push((kind, pattern.cells.clone()));
+                }
             }
         }
 
-        // Check col-wise win con
-        for i in 0..self.rows[0].len() {
-            if self.rows.iter().all(|r| r[i].marked) {
-                return true;
+        self.cell_lines.iter_mut().flatten().for_each(|l| l.clear());
+        self.lines.clear();
+        self.won = None;
+        // A line without cells, from an empty row, would be won before any number is called
+        let lines = lines.into_iter().filter(|(_, cells)| !cells.is_empty());
+        for (i, (kind, cells)) in lines.enumerate() {
+            let mut line = WinLine { kind, marked: 0, cells };
+            for &(r, c) in &line.cells {
+                self.cell_lines[r][c].push(i);
+                if self.rows[r][c].marked {
+                    line.marked += 1;
+                }
             }
+            if self.won.is_none() && line.is_complete() {
+                self.won = Some(i);
+            }
+            self.lines.push(line);
         }
+        Ok(())
+    }
 
-        false
+    pub fn has_won(&self) -> bool {
+        self.won.is_some()
     }
 
-    fn set_value(&mut self, value: usize) {
-        // TODO: index the boards for easier checking and setting
-        for row in self.rows.iter_mut() {
-            for v in row.iter_mut() {
-                if v.value == value {
-                    v.mark_seen();
+    /// The first line completed on this board
+    pub fn winning_line(&self) -> Option<WinningLine> {
+        self.won.map(|i| self.lines[i].kind)
+    }
+
+    /// Render the board as an aligned grid.
+    ///
+    /// With `color` set marked cells are bold and the winning line is bold green, otherwise
+    /// marked cells are wrapped in `[]` and the winning line in `<>`.
+    pub fn render(&self, color: bool) -> String {
+        let width =
+            self.rows.iter().flatten().map(|c| c.value.to_string().len()).max().unwrap_or(0);
+        let winning_cells = self.won.map_or(&[][..], |i| &self.lines[i].cells[..]);
+
+        let mut out = String::new();
+        for (r, row) in self.rows.iter().enumerate() {
+            let cells = row.iter().enumerate().map(|(c, cell)| {
+                let value = format!("{:>width$}", cell.value, width = width);
+                let winning = winning_cells.contains(&(r, c));
+                match (color, winning, cell.marked) {
+                    (true, true, _) => format!(" \x1b[1;32m{}\x1b[0m ", value),
+                    (true, false, true) => format!(" \x1b[1m{}\x1b[0m ", value),
+                    (false, true, _) => format!("<{}>", value),
+                    (false, false, true) => format!("[{}]", value),
+                    (_, false, false) => format!(" {} ", value),
+                }
+            });
+            out.push_str(cells.collect::<Vec<_>>().join(" ").trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Mark every cell holding `value`, returns true if this board has won.
+    pub fn set_value(&mut self, value: usize) -> bool {
+        let positions = match self.index.get(&value) {
+            Some(positions) => positions,
+            None => return self.has_won(),
+        };
+        for &(r, c) in positions {
+            let cell = &mut self.rows[r][c];
+            if cell.marked {
+                continue;
+            }
+            cell.mark_seen();
+            // Only the lines through this cell can have become complete
+            for &i in &self.cell_lines[r][c] {
+                let line = &mut self.lines[i];
+                line.marked += 1;
+                if self.won.is_none() && line.is_complete() {
+                    self.won = Some(i);
                 }
             }
         }
+        self.has_won()
+    }
+
+    /// Every value on the board in row order
+    pub fn values(&self) -> impl Iterator<Item = usize> + '_ {
+        self.rows.iter().flatten().map(|cell| cell.value)
+    }
+
+    /// The values in each line that wins this board
+    pub fn line_values(&self) -> Vec<(WinningLine, Vec<usize>)> {
+        self.lines
+            .iter()
+            .map(|line| {
+                (line.kind, line.cells.iter().map(|&(r, c)| self.rows[r][c].value).collect())
+            })
+            .collect()
     }
 
-    fn score_board(&self, last_called_value: usize) -> usize {
+    pub fn score_board(&self, last_called_value: usize) -> usize {
         // Sum all unmarked
         let mut sum = 0;
         for row in &self.rows {
@@ -172,15 +587,298 @@ impl FromStr for BingoBoard {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut rows = vec![];
-        for raw_row in s.split('\n') {
-            let mut row = vec![];
-            for raw_value in raw_row.split_ascii_whitespace() {
-                row.push(BCell::new(
-                    raw_value.parse::<usize>().map_err(|e| ParseError { msg: e.to_string() })?,
-                ));
+        for raw_row in s.lines().filter(|l| !l.trim().is_empty()) {
+            if rows.len() == MAX_SIDE {
+                return Err(too_many_rows());
             }
-            rows.push(row);
+            rows.push(parse_row(raw_row)?.into_iter().map(BCell::new).collect());
         }
-        Ok(BingoBoard { rows })
+        if rows.is_empty() {
+            return Err(ParseError { msg: String::from("Empty board") });
+        }
+        Ok(BingoBoard::new(rows))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WinningLine {
+    Row(usize),
+    Column(usize),
+    /// Top left to bottom right
+    Diagonal,
+    /// Top right to bottom left
+    AntiDiagonal,
+    Corners,
+    Blackout,
+    /// The nth pattern given
+    Pattern(usize),
+}
+
+impl fmt::Display for WinningLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WinningLine::Row(r) => write!(f, "row {}", r),
+            WinningLine::Column(c) => write!(f, "column {}", c),
+            WinningLine::Diagonal => write!(f, "diagonal"),
+            WinningLine::AntiDiagonal => write!(f, "anti-diagonal"),
+            WinningLine::Corners => write!(f, "four corners"),
+            WinningLine::Blackout => write!(f, "blackout"),
+            WinningLine::Pattern(i) => write!(f, "pattern {}", i),
+        }
+    }
+}
+
+/// Progress towards a single winning line
+#[derive(Debug, Clone)]
+struct WinLine {
+    kind: WinningLine,
+    /// (row, col) of each cell in the line
+    cells: Vec<(usize, usize)>,
+    /// Number of those cells marked
+    marked: usize,
+}
+
+impl WinLine {
+    fn is_complete(&self) -> bool {
+        self.marked == self.cells.len()
+    }
+}
+
+/// How and when a board won
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardResult {
+    /// Index of the board in the input
+    pub board: usize,
+    /// Index of the winning draw
+    pub draw: usize,
+    /// The winning number
+    pub number: usize,
+    pub line: WinningLine,
+    pub score: usize,
+}
+
+impl fmt::Display for BoardResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Board {} won on draw {} (number {}) via {} with score {}",
+            self.board, self.draw, self.number, self.line, self.score
+        )
+    }
+}
+
+/// All boards in play, indexed by value so a call only touches the boards holding it.
+#[derive(Debug, Clone)]
+pub struct BingoHall {
+    pub boards: Vec<BingoBoard>,
+    /// Value -> index of every board holding that value
+    index: HashMap<usize, Vec<usize>>,
+}
+
+impl BingoHall {
+    pub fn new(boards: Vec<BingoBoard>) -> Self {
+        let mut index: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, board) in boards.iter().enumerate() {
+            for &value in board.index.keys() {
+                index.entry(value).or_default().push(i);
+            }
+        }
+        Self { boards, index }
+    }
+
+    /// Call `value` on every board holding it, returns the boards that won on this call in board
+    /// order.
+    pub fn call(&mut self, value: usize) -> Vec<usize> {
+        let mut winners = vec![];
+        if let Some(boards) = self.index.get(&value) {
+            for &i in boards {
+                let board = &mut self.boards[i];
+                let already_won = board.has_won();
+                if board.set_value(value) && !already_won {
+                    winners.push(i);
+                }
+            }
+        }
+        winners
+    }
+
+    /// Call every number in order, returns a result for each board that won in the order they won.
+    pub fn play(&mut self, numbers: &[usize]) -> Vec<BoardResult> {
+        let mut results = vec![];
+        for (draw, &number) in numbers.iter().enumerate() {
+            for board in self.call(number) {
+                let b = &self.boards[board];
+                results.push(BoardResult {
+                    board,
+                    draw,
+                    number,
+                    line: b.winning_line().expect("Winning board has a winning line"),
+                    score: b.score_board(number),
+                });
+            }
+            if results.len() == self.boards.len() {
+                break;
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+
+22 13 17 11  0
+ 8  2 23  4 24
+21  9 14 16  7
+ 6 10  3 18  5
+ 1 12 20 15 19
+
+ 3 15  0  2 22
+ 9 18 13 17  5
+19  8  7 25 23
+20 11 10 24  4
+14 21 16 12  6
+
+14 21 17 24  4
+10 16 15  9 19
+18  8 23 26 20
+22 11 13  6  5
+ 2  0 12  3  7";
+
+    fn example() -> (Numbers, Vec<BingoBoard>) {
+        let mut blocks = EXAMPLE.split("\n\n");
+        let numbers = blocks.next().unwrap().parse::<Numbers>().unwrap();
+        let boards = blocks.map(|b| b.parse::<BingoBoard>().unwrap()).collect();
+        (numbers, boards)
+    }
+
+    #[test]
+    fn test_play_example() {
+        let (numbers, boards) = example();
+        let results = BingoHall::new(boards).play(&numbers.0);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0],
+            BoardResult { board: 2, draw: 11, number: 24, line: WinningLine::Row(0), score: 4512 }
+        );
+        assert_eq!(results[2].board, 1);
+        assert_eq!(results[2].number, 13);
+        assert_eq!(results[2].score, 1924);
+    }
+
+    #[test]
+    fn test_winning_lines() {
+        let mut board = "1 2\n3 4".parse::<BingoBoard>().unwrap();
+        assert!(!board.set_value(2));
+        assert!(board.set_value(1));
+        assert_eq!(board.winning_line(), Some(WinningLine::Row(0)));
+
+        let mut board = "1 2\n3 4".parse::<BingoBoard>().unwrap();
+        board.set_value(2);
+        assert!(board.set_value(4));
+        assert_eq!(board.winning_line(), Some(WinningLine::Column(1)));
+        assert_eq!(board.score_board(4), 16);
+    }
+
+    #[test]
+    fn test_win_rules() {
+        let mut board = "1 2 3\n4 5 6\n7 8 9".parse::<BingoBoard>().unwrap();
+
+        let mut diag = board.clone();
+        diag.set_rules(&[WinRule::Diagonals]).unwrap();
+        assert!(!diag.set_value(1) && !diag.set_value(2) && !diag.set_value(3));
+        assert!(!diag.set_value(5));
+        assert!(diag.set_value(7));
+        assert_eq!(diag.winning_line(), Some(WinningLine::AntiDiagonal));
+
+        let mut corners = board.clone();
+        corners.set_rules(&[WinRule::Corners, WinRule::Blackout]).unwrap();
+        [1, 3, 7].iter().for_each(|&v| assert!(!corners.set_value(v)));
+        assert!(corners.set_value(9));
+        assert_eq!(corners.winning_line(), Some(WinningLine::Corners));
+
+        let pattern = ".X.\nXXX\n.X.".parse::<Pattern>().unwrap();
+        let mut plus = board.clone();
+        plus.set_rules(&[WinRule::Rows, WinRule::Pattern(pattern)]).unwrap();
+        [2, 4, 6, 8].iter().for_each(|&v| assert!(!plus.set_value(v)));
+        assert!(plus.set_value(5));
+        assert_eq!(plus.winning_line(), Some(WinningLine::Row(1)));
+
+        let too_big = "XXXX".parse::<Pattern>().unwrap();
+        assert!(board.set_rules(&[WinRule::Pattern(too_big)]).is_err());
+    }
+
+    #[test]
+    fn test_pattern_lines() {
+        let padded = "\n\nX.\n.X\n\n".parse::<Pattern>().unwrap();
+        assert_eq!(padded, "X.\n.X".parse::<Pattern>().unwrap());
+        assert_eq!(
+            "X.\n\n.X".parse::<Pattern>().unwrap_err().to_string(),
+            "Error for command: Blank line 2 inside the pattern"
+        );
+        assert_eq!(
+            "\nX.\n.Y".parse::<Pattern>().unwrap_err().to_string(),
+            "Error for command: Invalid pattern character 'Y' at line 3"
+        );
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let opts = WinOpts { win: vec![], pattern: vec![] };
+        assert_eq!(opts.rules().unwrap(), vec![WinRule::Rows, WinRule::Columns]);
+
+        let path = std::env::temp_dir().join(format!("day4-pattern-{}", std::process::id()));
+        std::fs::write(&path, "X.\n.X\n").unwrap();
+        let opts = WinOpts { win: vec![], pattern: vec![path.clone()] };
+        let rules = opts.rules().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(rules, vec![WinRule::Pattern("X.\n.X".parse().unwrap())]);
+    }
+
+    #[test]
+    fn test_render() {
+        let mut board = " 1 2\n 3 40".parse::<BingoBoard>().unwrap();
+        board.set_value(3);
+        assert_eq!(board.to_string(), "  1    2\n[ 3]  40\n");
+        board.set_value(40);
+        assert_eq!(board.to_string(), "  1    2\n< 3> <40>\n");
+        assert_eq!(
+            board.render(true).lines().nth(1),
+            Some(" \x1b[1;32m 3\x1b[0m   \x1b[1;32m40\x1b[0m")
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let numbers = "1,2,3,4,5".parse::<Numbers>().unwrap();
+        let good = vec![(2, vec![1, 2]), (3, vec![3, 4])];
+        let ragged = vec![(5, vec![1, 2]), (6, vec![3])];
+        let duplicate = vec![(8, vec![1, 2]), (9, vec![2, 6])];
+        let issues = validate(&numbers, &[good, ragged, duplicate]);
+
+        let issues: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "Board 1 at line 6: row has 1 values, expected 2",
+                "Board 2 at line 9: value 2 appears more than once",
+                "Board 2 at line 9: value 6 is never drawn",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_value_limits() {
+        assert!("1,100000".parse::<Numbers>().is_err());
+        assert!("18446744073709551615 1".parse::<BingoBoard>().is_err());
+        assert!(vec!["1"; MAX_SIDE + 1].join(" ").parse::<BingoBoard>().is_err());
+        assert!("1\n".repeat(MAX_SIDE + 1).parse::<BingoBoard>().is_err());
+
+        let board = "99999 99999\n99999 99999".parse::<BingoBoard>().unwrap();
+        assert_eq!(board.score_board(MAX_VALUE), 4 * 99_999 * 99_999);
     }
 }
@@ -0,0 +1,422 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use clap::{ArgEnum, Parser};
+
+use super::{
+    day4a::{read_file, BingoBoard, BingoHall, Numbers, WinOpts},
+    CommandImpl, DynError,
+};
+
+/// Search for a draw order, taken from the input draws, that makes a chosen board win first
+#[derive(Parser, Debug)]
+pub struct Day4Search {
+    #[clap(long, short)]
+    input: PathBuf,
+    /// Zero based index of the board that must win first
+    #[clap(long, short)]
+    board: usize,
+    #[clap(long, arg_enum, default_value = "shortest")]
+    objective: Objective,
+    #[clap(flatten)]
+    win: WinOpts,
+    /// Warn about malformed boards instead of failing
+    #[clap(long)]
+    lenient: bool,
+    /// Give up the min-score search after trying this many sets of draws
+    #[clap(long, default_value = "1000000")]
+    max_nodes: usize,
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Objective {
+    /// The fewest draws
+    Shortest,
+    /// The highest final score
+    MaxScore,
+    /// The lowest final score
+    MinScore,
+}
+
+impl CommandImpl for Day4Search {
+    fn main(&self) -> Result<(), DynError> {
+        let (numbers, mut boards) = read_file(&self.input, self.lenient)?;
+        self.win.apply(&mut boards)?;
+        if self.board >= boards.len() {
+            return Err(format!(
+                "Board {} does not exist, there are {} boards",
+                self.board,
+                boards.len()
+            )
+            .into());
+        }
+
+        let mut search = Search::new(&numbers, &boards, self.board);
+        let draws = match search.solve(self.objective, self.max_nodes)? {
+            Some(draws) => draws,
+            None => {
+                println!("Board {} can never win first with these draws", self.board);
+                return Ok(());
+            }
+        };
+
+        // Replay the draws through the engine to check the board really wins first
+        let results = BingoHall::new(boards).play(&draws);
+        let result = results
+            .first()
+            .filter(|r| r.board == self.board && r.draw + 1 == draws.len())
+            .ok_or("Search produced a draw order where the board does not win first")?;
+
+        let order: Vec<String> = draws.iter().map(|d| d.to_string()).collect();
+        println!("Draws ({}): {}", draws.len(), order.join(","));
+        println!("{}", result);
+        Ok(())
+    }
+}
+
+/// State for searching over sets of drawn values.
+///
+/// A board wins when every distinct value on one of its lines has been drawn, so the search
+/// only needs to track how many values of each line are still undrawn.
+struct Search {
+    target: usize,
+    /// The board owning each line and the distinct values on that line
+    lines: Vec<(usize, Vec<usize>)>,
+    /// Value -> index into `lines` of every line holding that value
+    value_lines: HashMap<usize, Vec<usize>>,
+    /// Value -> number of cells holding it on the target board
+    target_cells: HashMap<usize, usize>,
+    /// Sum of every cell on the target board
+    total: usize,
+    /// Values that may be drawn
+    pool: HashSet<usize>,
+    /// Number of undrawn values on each line
+    remaining: Vec<usize>,
+}
+
+/// A draw order along with the score of the target board when it wins
+struct Candidate {
+    draws: Vec<usize>,
+    score: usize,
+}
+
+impl Search {
+    fn new(numbers: &Numbers, boards: &[BingoBoard], target: usize) -> Self {
+        let mut lines = vec![];
+        let mut value_lines: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (b, board) in boards.iter().enumerate() {
+            for (_, mut values) in board.line_values() {
+                values.sort_unstable();
+                values.dedup();
+                values.iter().for_each(|&v| value_lines.entry(v).or_default().push(lines.len()));
+                lines.push((b, values));
+            }
+        }
+        let mut target_cells = HashMap::new();
+        boards[target].values().for_each(|v| *target_cells.entry(v).or_insert(0) += 1);
+        let remaining = lines.iter().map(|(_, values)| values.len()).collect();
+        let total = boards[target].values().sum();
+
+        Self {
+            target,
+            lines,
+            value_lines,
+            target_cells,
+            total,
+            pool: numbers.0.iter().copied().collect(),
+            remaining,
+        }
+    }
+
+    /// The best draw order for `objective`, or an error if the min-score search tries more than
+    /// `max_nodes` sets of draws
+    fn solve(
+        &mut self,
+        objective: Objective,
+        max_nodes: usize,
+    ) -> Result<Option<Vec<usize>>, String> {
+        let mut best: Option<Candidate> = None;
+        let mut nodes = 0;
+        let target_lines: Vec<Vec<usize>> = self
+            .lines
+            .iter()
+            .filter(|(b, values)| *b == self.target && values.iter().all(|v| self.pool.contains(v)))
+            .map(|(_, values)| values.clone())
+            .collect();
+
+        for line in &target_lines {
+            let (extras, still_possible) = match objective {
+                Objective::MinScore => self.extras(line),
+                _ => (vec![], vec![0]),
+            };
+            // Try each value of the line as the winning draw
+            for &last in line {
+                let drawn: Vec<usize> = line.iter().copied().filter(|&v| v != last).collect();
+                self.reset();
+                let mut ok = true;
+                for &value in &drawn {
+                    ok &= self.draw(value);
+                }
+                if !ok || !self.wins_first(last) {
+                    continue;
+                }
+
+                if objective == Objective::MinScore {
+                    nodes += self
+                        .minimize(
+                            &extras,
+                            &still_possible,
+                            &drawn,
+                            last,
+                            &mut best,
+                            max_nodes - nodes,
+                        )
+                        .ok_or_else(|| {
+                            format!("No min score found within {} sets of draws", max_nodes)
+                        })?;
+                    continue;
+                }
+                let mut draws = drawn;
+                draws.push(last);
+                let candidate = Candidate { score: self.unmarked(&draws) * last, draws };
+                let better = best.as_ref().map_or(true, |b| match objective {
+                    Objective::Shortest => {
+                        (candidate.draws.len(), b.score) < (b.draws.len(), candidate.score)
+                    }
+                    _ => candidate.score > b.score,
+                });
+                if better {
+                    best = Some(candidate);
+                }
+            }
+        }
+        Ok(best.map(|b| b.draws))
+    }
+
+    /// Values on the target board outside `line` that may be drawn, heaviest first, along with
+    /// the weight of every extra from each index on, the most drawing them could remove
+    fn extras(&self, line: &[usize]) -> (Vec<usize>, Vec<usize>) {
+        let line: HashSet<usize> = line.iter().copied().collect();
+        let mut extras: Vec<usize> = self
+            .target_cells
+            .keys()
+            .copied()
+            .filter(|v| !line.contains(v) && self.pool.contains(v))
+            .collect();
+        extras.sort_unstable_by_key(|v| std::cmp::Reverse(self.weight(*v)));
+        let mut still_possible = vec![0; extras.len() + 1];
+        for i in (0..extras.len()).rev() {
+            still_possible[i] = still_possible[i + 1] + self.weight(extras[i]);
+        }
+        (extras, still_possible)
+    }
+
+    /// Branch and bound over which extra values to draw before the line to lower the score,
+    /// returning the number of sets of draws tried, or `None` after more than `max_nodes`.
+    ///
+    /// Every value in `line` has already been drawn and still lets the target win first on
+    /// `last`. The search keeps its own stack, as a large lenient board can have thousands of
+    /// extras.
+    fn minimize(
+        &mut self,
+        extras: &[usize],
+        still_possible: &[usize],
+        line: &[usize],
+        last: usize,
+        best: &mut Option<Candidate>,
+        max_nodes: usize,
+    ) -> Option<usize> {
+        let mut line_draws = line.to_vec();
+        line_draws.push(last);
+        let mut unmarked = self.unmarked(&line_draws);
+
+        // Indices of the extras drawn so far, and the next index to try after the last of them
+        let mut chosen: Vec<usize> = vec![];
+        let mut next = 0;
+        let mut nodes = 1;
+        if nodes > max_nodes {
+            return None;
+        }
+        // Drawing another extra only lowers the score, so a set is only worth recording when
+        // none of the extras after it could be drawn
+        let mut leaf = true;
+        loop {
+            // Even drawing every remaining extra can't beat the best so far
+            let pruned = best
+                .as_ref()
+                .map_or(false, |b| (unmarked - still_possible[next]) * last >= b.score);
+            if next < extras.len() && !pruned {
+                nodes += 1;
+                if nodes > max_nodes {
+                    return None;
+                }
+                let value = extras[next];
+                next += 1;
+                if self.draw(value) && self.wins_first(last) {
+                    chosen.push(next - 1);
+                    unmarked -= self.weight(value);
+                    leaf = true;
+                } else {
+                    self.undraw(value);
+                }
+                continue;
+            }
+
+            // Every extra from here is tried or pruned, so backtrack
+            if leaf && best.as_ref().map_or(true, |b| unmarked * last < b.score) {
+                let mut draws: Vec<usize> = chosen.iter().map(|&i| extras[i]).collect();
+                draws.extend_from_slice(&line_draws);
+                *best = Some(Candidate { draws, score: unmarked * last });
+            }
+            leaf = false;
+            match chosen.pop() {
+                Some(i) => {
+                    self.undraw(extras[i]);
+                    unmarked += self.weight(extras[i]);
+                    next = i + 1;
+                }
+                None => return Some(nodes),
+            }
+        }
+    }
+
+    /// Total of the cells on the target board holding `value`
+    fn weight(&self, value: usize) -> usize {
+        value * self.target_cells.get(&value).copied().unwrap_or(0)
+    }
+
+    /// Sum of the target board cells left unmarked after `drawn`
+    fn unmarked(&self, drawn: &[usize]) -> usize {
+        self.total - drawn.iter().map(|&v| self.weight(v)).sum::<usize>()
+    }
+
+    fn reset(&mut self) {
+        for (remaining, (_, values)) in self.remaining.iter_mut().zip(&self.lines) {
+            *remaining = values.len();
+        }
+    }
+
+    /// Draw `value`, returns false if that completes any line
+    fn draw(&mut self, value: usize) -> bool {
+        let mut ok = true;
+        for &i in self.value_lines.get(&value).into_iter().flatten() {
+            self.remaining[i] -= 1;
+            ok &= self.remaining[i] > 0;
+        }
+        ok
+    }
+
+    fn undraw(&mut self, value: usize) {
+        for &i in self.value_lines.get(&value).into_iter().flatten() {
+            self.remaining[i] += 1;
+        }
+    }
+
+    /// True if drawing `last` now would not complete a line on any other board
+    fn wins_first(&self, last: usize) -> bool {
+        self.value_lines
+            .get(&last)
+            .into_iter()
+            .flatten()
+            .all(|&i| self.lines[i].0 == self.target || self.remaining[i] > 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn boards(raw: &[&str]) -> Vec<BingoBoard> {
+        raw.iter().map(|b| b.parse::<BingoBoard>().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_search() {
+        let numbers = "1,2,3,4,5,6".parse::<Numbers>().unwrap();
+        let boards = boards(&["1 2\n3 4", "1 3\n5 6"]);
+
+        let shortest =
+            Search::new(&numbers, &boards, 0).solve(Objective::Shortest, 1000).unwrap().unwrap();
+        assert_eq!(shortest.len(), 2);
+        // Column 0 of board 0 is row 0 of board 1
+        assert_ne!(shortest, vec![1, 3]);
+        assert_ne!(shortest, vec![3, 1]);
+
+        // (1 + 3) * 4 from drawing 2 then 4
+        let max =
+            Search::new(&numbers, &boards, 0).solve(Objective::MaxScore, 1000).unwrap().unwrap();
+        assert_eq!(max, vec![2, 4]);
+
+        // 1 * 4, only one extra can be drawn without completing a line early
+        let min =
+            Search::new(&numbers, &boards, 0).solve(Objective::MinScore, 1000).unwrap().unwrap();
+        let results = BingoHall::new(boards).play(&min);
+        assert_eq!((results[0].board, results[0].score), (0, 4));
+    }
+
+    #[test]
+    fn test_search_impossible() {
+        let numbers = "1,2,3,4".parse::<Numbers>().unwrap();
+        let boards = boards(&["1 2\n3 4", "4 3\n2 1"]);
+        assert!(Search::new(&numbers, &boards, 1)
+            .solve(Objective::Shortest, 1000)
+            .unwrap()
+            .is_none());
+    }
+
+    /// A 6x6 board of 1 to 36 and a board sharing some of its values, with only the values in
+    /// `pool` drawn
+    fn six_by_six(pool: usize) -> (Numbers, Vec<BingoBoard>) {
+        let rows: Vec<String> = (0..6)
+            .map(|r| (1..=6).map(|c| (r * 6 + c).to_string()).collect::<Vec<_>>().join(" "))
+            .collect();
+        let other = "1 2 7 8 3 9\n".to_string()
+            + &(1..6)
+                .map(|r| {
+                    (0..6).map(|c| (100 + r * 6 + c).to_string()).collect::<Vec<_>>().join(" ")
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+        let numbers = (1..=pool).map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        (numbers.parse().unwrap(), boards(&[&rows.join("\n"), &other]))
+    }
+
+    #[test]
+    fn test_min_score_large_board() {
+        let (numbers, boards) = six_by_six(10);
+        let min = Search::new(&numbers, &boards, 0).solve(Objective::MinScore, 1000).unwrap();
+        let results = BingoHall::new(boards.clone()).play(&min.unwrap());
+        assert_eq!(results[0].board, 0);
+
+        // Every set of drawn values, with each one as the winning draw
+        let mut lowest = usize::MAX;
+        for set in 1..1usize << 10 {
+            let drawn: Vec<usize> = (0..10).filter(|i| set >> i & 1 == 1).map(|i| i + 1).collect();
+            for (i, &last) in drawn.iter().enumerate() {
+                let mut draws = drawn.clone();
+                draws.remove(i);
+                draws.push(last);
+                // Winning first means winning alone, not tying with board 1 on the last draw
+                let results = BingoHall::new(boards.clone()).play(&draws);
+                let winners: Vec<usize> = results.iter().map(|r| r.board).collect();
+                if winners == [0] && results[0].draw == draws.len() - 1 {
+                    lowest = lowest.min(results[0].score);
+                }
+            }
+        }
+        assert_eq!(results[0].score, lowest);
+    }
+
+    #[test]
+    fn test_min_score_node_limit() {
+        let (numbers, boards) = six_by_six(36);
+        let mut search = Search::new(&numbers, &boards, 0);
+        assert_eq!(
+            search.solve(Objective::MinScore, 10),
+            Err(String::from("No min score found within 10 sets of draws"))
+        );
+        assert!(Search::new(&numbers, &boards, 0).solve(Objective::MinScore, 100_000).is_ok());
+    }
+}
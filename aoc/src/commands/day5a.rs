@@ -1,4 +1,3 @@
-#![allow(dead_code)]
 use std::{collections::HashMap, path::PathBuf, str::FromStr};
 
 use clap::Parser;
@@ -16,110 +15,83 @@ pub struct Day5a {
 impl CommandImpl for Day5a {
     fn main(&self) -> Result<(), DynError> {
         let lines: Vec<Line> = slurp_file(&self.input)?;
-        let mut seen_points = HashMap::new();
+        println!("Answer: {}", count_overlaps(&lines, false));
+        Ok(())
+    }
+}
 
-        for line in lines.iter().filter(|l| l.is_horizontal() || l.is_vertical()) {
-            println!("{:?}", line);
-        }
+/// Largest coordinate accepted, the puzzle itself stays below 1000
+pub const MAX_COORD: i64 = 9999;
 
-        for line in &lines {
-            if line.is_horizontal() {
-                let mut x = line.start.x;
-                let y = line.start.y;
-                while x <= line.stop.x {
-                    let point = Point::new(x, y);
-                    let p = seen_points
-                        .entry(point.as_cantor_pairing() as i64)
-                        .or_insert_with(|| Point::new(x, y));
-                    p.counter += 1;
-                    x += 1;
-                }
-            } else if line.is_vertical() {
-                let x = line.start.x;
-                let mut y = line.start.y;
-                while y <= line.stop.y {
-                    let point = Point::new(x, y);
-                    let p = seen_points
-                        .entry(point.as_cantor_pairing() as i64)
-                        .or_insert_with(|| Point::new(x, y));
-                    p.counter += 1;
-                    y += 1;
-                }
-            }
+/// Count the points covered by two or more lines, skipping diagonal lines unless `diagonals`
+pub fn count_overlaps(lines: &[Line], diagonals: bool) -> usize {
+    let mut seen_points: HashMap<(i64, i64), usize> = HashMap::new();
+
+    for line in lines {
+        if !diagonals && !line.is_horizontal() && !line.is_vertical() {
+            continue;
         }
-        // count the number of keys that have counter of two
-        let mut total = 0;
-        for (_k, v) in seen_points.into_iter() {
-            if v.counter >= 2 {
-                total += 1;
-            }
+        // Lines are horizontal, vertical or at 45 degrees, so each step moves by at most one
+        // in x and y
+        let dx = (line.stop.x - line.start.x).signum();
+        let dy = (line.stop.y - line.start.y).signum();
+        let steps = (line.stop.x - line.start.x).abs().max(line.stop.y - line.start.y);
+        for i in 0..=steps {
+            *seen_points.entry((line.start.x + dx * i, line.start.y + dy * i)).or_default() += 1;
         }
-        println!("Answer: {}", total);
-        Ok(())
     }
+    seen_points.values().filter(|&&count| count >= 2).count()
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Point {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
     x: i64,
     y: i64,
-    counter: usize,
-}
-
-impl Point {
-    fn new(x: i64, y: i64) -> Self {
-        Self { x, y, counter: 0 }
-    }
-
-    fn as_cantor_pairing(&self) -> f64 {
-        (((self.x + self.y) * (self.x + self.y + 1)) as f64 / 2.0) + self.y as f64
-    }
-
-    fn from_cantor_pairing(pairing: f64) -> Self {
-        let w = (((8.0 * pairing + 1.0).sqrt() - 1.0) / 2.0).floor();
-        let t = (w.powi(2) + w) / 2.0;
-        let y = pairing - t;
-        let x = w - y;
-        Self::new(x as i64, y as i64)
-    }
 }
 
 impl FromStr for Point {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut values = s.split(',');
-        let x = values
-            .next()
-            .expect("Missing x")
-            .parse::<i64>()
-            .map_err(|e| ParseError::new(e.to_string()))?;
-        let y = values
-            .next()
-            .expect("Missing y")
-            .parse::<i64>()
-            .map_err(|e| ParseError::new(e.to_string()))?;
-        Ok(Point::new(x, y))
+        let (x, y) =
+            s.split_once(',').ok_or_else(|| ParseError::new(format!("Missing y in {:?}", s)))?;
+        let coord = |name, value: &str| {
+            let value = value
+                .trim()
+                .parse::<i64>()
+                .map_err(|e| ParseError::new(format!("Invalid {} {:?}: {}", name, value, e)))?;
+            if !(0..=MAX_COORD).contains(&value) {
+                return Err(ParseError::new(format!(
+                    "{} {} is outside 0 to {}",
+                    name, value, MAX_COORD
+                )));
+            }
+            Ok(value)
+        };
+        Ok(Point { x: coord("x", x)?, y: coord("y", y)? })
     }
 }
 
+/// A horizontal, vertical or 45 degree line, stored with `start` above `stop`, or to the left
+/// of it on a horizontal line
 #[derive(Debug, Clone, Copy)]
-struct Line {
+pub struct Line {
     start: Point,
     stop: Point,
-    c_start: f64,
-    c_stop: f64,
 }
 
 impl Line {
-    fn new(start: Point, stop: Point) -> Self {
-        let c_start = start.as_cantor_pairing();
-        let c_stop = stop.as_cantor_pairing();
-        // Force "sorted" order
-        if c_start < c_stop {
-            Self { start, stop, c_start, c_stop }
+    fn new(start: Point, stop: Point) -> Result<Self, ParseError> {
+        let (dx, dy) = (stop.x - start.x, stop.y - start.y);
+        if dx != 0 && dy != 0 && dx.abs() != dy.abs() {
+            return Err(ParseError::new(String::from(
+                "Line is not horizontal, vertical or at 45 degrees",
+            )));
+        }
+        if (start.y, start.x) <= (stop.y, stop.x) {
+            Ok(Self { start, stop })
         } else {
-            Self { start: stop, stop: start, c_start: c_stop, c_stop: c_start }
+            Ok(Self { start: stop, stop: start })
         }
     }
 
@@ -130,34 +102,79 @@ impl Line {
     fn is_horizontal(&self) -> bool {
         self.start.y == self.stop.y
     }
-
-    fn slope(&self) -> f64 {
-        (self.stop.y - self.start.y) as f64 / (self.stop.x - self.start.x) as f64
-    }
-
-    // fn walk_points(&self) {
-    //     // y = mx + b
-    //     let mut x = self.start.x;
-    //     let mut y = self.start.y;
-
-    // }
 }
 
 impl FromStr for Line {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut points = s.split(" -> ");
-        let start = points
-            .next()
-            .expect("Missing Line Start")
-            .parse::<Point>()
-            .map_err(|e| ParseError::new(e.to_string()))?;
-        let stop = points
-            .next()
-            .expect("Missing Line Stop")
-            .parse::<Point>()
-            .map_err(|e| ParseError::new(e.to_string()))?;
-        Ok(Self::new(start, stop))
+        let (start, stop) = s
+            .split_once(" -> ")
+            .ok_or_else(|| ParseError::new(format!("Missing line stop in {:?}", s)))?;
+        Self::new(start.parse()?, stop.parse()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Horizontal, vertical and 45 degree lines within a small grid
+    fn line() -> impl Strategy<Value = (i64, i64, i64, i64)> {
+        (0..20i64, 0..20i64, 0..4usize, 0..20i64, prop::bool::ANY).prop_map(
+            |(x, y, kind, len, reversed)| {
+                let (dx, dy) = [(1, 0), (0, 1), (1, 1), (1, -1)][kind];
+                // Lines heading up stop at the top edge rather than leaving the grid
+                let len = if dy < 0 { len.min(y) } else { len };
+                let (x2, y2) = (x + dx * len, y + dy * len);
+                if reversed {
+                    (x2, y2, x, y)
+                } else {
+                    (x, y, x2, y2)
+                }
+            },
+        )
+    }
+
+    fn dense_overlaps(lines: &[(i64, i64, i64, i64)], diagonals: bool) -> usize {
+        let mut grid = vec![[0u32; 40]; 40];
+        for &(x1, y1, x2, y2) in lines {
+            if x1 != x2 && y1 != y2 && !diagonals {
+                continue;
+            }
+            let (dx, dy) = ((x2 - x1).signum(), (y2 - y1).signum());
+            for i in 0..=(x2 - x1).abs().max((y2 - y1).abs()) {
+                grid[(y1 + dy * i) as usize][(x1 + dx * i) as usize] += 1;
+            }
+        }
+        grid.iter().flatten().filter(|&&c| c >= 2).count()
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        assert!("-5,0 -> -1,0".parse::<Line>().is_err());
+        assert!("0,0 -> 10000,0".parse::<Line>().is_err());
+        assert!("0,0 -> 1000000000,1000000000".parse::<Line>().is_err());
+        assert!("0,0 -> 2,1".parse::<Line>().is_err());
+        assert!("0,0 -> 2".parse::<Line>().is_err());
+
+        let lines: Vec<Line> =
+            ["5,0 -> 1,0", "3,0 -> 3,5", "0,4 -> 4,0"].iter().map(|l| l.parse().unwrap()).collect();
+        assert_eq!(count_overlaps(&lines, false), 1);
+        assert_eq!(count_overlaps(&lines, true), 3);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_overlaps_match_dense_grid(raw in prop::collection::vec(line(), 0..30)) {
+            let lines: Vec<Line> = raw
+                .iter()
+                .map(|(x1, y1, x2, y2)| format!("{},{} -> {},{}", x1, y1, x2, y2).parse().unwrap())
+                .collect();
+            for diagonals in [false, true] {
+                prop_assert_eq!(count_overlaps(&lines, diagonals), dense_overlaps(&raw, diagonals));
+            }
+        }
     }
 }
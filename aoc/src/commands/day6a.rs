@@ -1,42 +1,71 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use clap::Parser;
+use clap::{ArgEnum, Parser};
 
 use crate::utils::{slurp_file, ParseError};
 
-use super::{CommandImpl, DynError};
+use super::{day6b::simulate_buckets, CommandImpl, DynError};
 
 #[derive(Parser, Debug)]
 pub struct Day6a {
     #[clap(long, short)]
     input: PathBuf,
+    /// Number of days to simulate
+    #[clap(long, short, default_value = "80")]
+    days: usize,
+    /// Simulation engine, the naive engine grows one entry per fish
+    #[clap(long, arg_enum, default_value = "bucket")]
+    engine: Engine,
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    Naive,
+    Bucket,
 }
 
 impl CommandImpl for Day6a {
     fn main(&self) -> Result<(), DynError> {
-        println!("EX: {:?}", self.input);
-        let mut starter_fish: LanternFish = slurp_file(&self.input)?.remove(0);
-
-        for _i in 0..80 {
-            let mut new = vec![];
-            for fish in &mut starter_fish.0 {
-                if *fish > 0 {
-                    *fish -= 1;
-                } else {
-                    *fish = 6;
-                    new.push(8);
-                }
+        let starter_fish = read_fish(&self.input)?;
+        let answer = match self.engine {
+            Engine::Naive => simulate_naive(&starter_fish, self.days) as u128,
+            Engine::Bucket => simulate_buckets(&starter_fish, self.days)?,
+        };
+        println!("Answer: {}", answer);
+        Ok(())
+    }
+}
+
+/// Read the timers from the first line of the file at `path`
+pub fn read_fish(path: &Path) -> Result<LanternFish, DynError> {
+    let fish = slurp_file(path)?.into_iter().next();
+    Ok(fish.ok_or_else(|| ParseError::new(String::from("Input is empty")))?)
+}
+
+/// Simulate by tracking every fish individually, memory grows exponentially with `days`.
+pub fn simulate_naive(fish: &LanternFish, days: usize) -> usize {
+    let mut fish = fish.0.clone();
+    for _i in 0..days {
+        let mut new = vec![];
+        for fish in &mut fish {
+            if *fish > 0 {
+                *fish -= 1;
+            } else {
+                *fish = 6;
+                new.push(8);
             }
-            starter_fish.0.extend(new.into_iter());
         }
-        println!("Answer: {}", starter_fish.0.len());
-        Ok(())
+        fish.extend(new);
     }
+    fish.len()
 }
 
-/// The called bingo numbers
+/// The timers of the starting school of fish
 #[derive(Debug, Clone)]
-struct LanternFish(Vec<usize>);
+pub struct LanternFish(pub Vec<usize>);
 
 impl FromStr for LanternFish {
     type Err = ParseError;
@@ -44,7 +73,14 @@ impl FromStr for LanternFish {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut values = vec![];
         for v in s.split(',') {
-            values.push(v.parse::<usize>().map_err(|e| ParseError::new(e.to_string()))?);
+            let timer = v
+                .trim()
+                .parse::<usize>()
+                .map_err(|e| ParseError::new(format!("Invalid timer {:?}: {}", v, e)))?;
+            if timer > 8 {
+                return Err(ParseError::new(format!("Timer {} is out of range 0 to 8", timer)));
+            }
+            values.push(timer);
         }
         Ok(LanternFish(values))
     }
@@ -1,77 +1,109 @@
-use std::{collections::VecDeque, path::PathBuf, str::FromStr};
+use std::{collections::VecDeque, path::PathBuf};
 
 use clap::Parser;
 
-use crate::utils::{slurp_file, ParseError};
-
-use super::{CommandImpl, DynError};
+use super::{
+    day6a::{read_fish, LanternFish},
+    CommandImpl, DynError,
+};
 
 #[derive(Parser, Debug)]
 pub struct Day6b {
     #[clap(long, short)]
     input: PathBuf,
+    /// Number of days to simulate
+    #[clap(long, short, default_value = "256")]
+    days: usize,
 }
 
 impl CommandImpl for Day6b {
     fn main(&self) -> Result<(), DynError> {
-        let starter_fish: LanternFish = slurp_file(&self.input)?.remove(0);
+        let starter_fish = read_fish(&self.input)?;
+        println!("Answer: {}", simulate_buckets(&starter_fish, self.days)?);
+        Ok(())
+    }
+}
 
-        let mut lookup = VecDeque::new();
-        for i in 0..=6 {
-            lookup.push_back(FishCounter { generations: i, total_fish: 0 });
-        }
+/// Simulate by counting the fish at each timer value, independent of the size of the school.
+///
+/// Fails once the school no longer fits in a `u128`, which takes roughly a thousand days.
+pub fn simulate_buckets(fish: &LanternFish, days: usize) -> Result<u128, DynError> {
+    let overflow = || format!("The school of fish outgrows 128 bits within {} days", days);
+    let mut lookup = VecDeque::new();
+    for i in 0..=6 {
+        lookup.push_back(FishCounter { generations: i, total_fish: 0 });
+    }
 
-        for fish in starter_fish.0 {
-            lookup[fish].total_fish += 1;
-        }
+    let mut hatchery = VecDeque::new();
+    for i in 7..=8 {
+        hatchery.push_back(FishCounter { generations: i, total_fish: 0 });
+    }
 
-        let mut hatchery = VecDeque::new();
-        for i in 7..=8 {
-            hatchery.push_back(FishCounter { generations: i, total_fish: 0 });
+    for &fish in &fish.0 {
+        // Timers of 7 and 8 are still young fish in the hatchery
+        if fish <= 6 {
+            lookup[fish].total_fish += 1;
+        } else {
+            hatchery[fish - 7].total_fish += 1;
         }
+    }
 
-        for _i in 0..256 {
-            // Add new fish
-            let new_fish_to_add = lookup[0].total_fish;
-            let young_fish_to_promote = hatchery[0].total_fish;
-
-            lookup.rotate_left(1);
-            for (i, group) in lookup.iter_mut().enumerate() {
-                group.generations = i;
-            }
-            lookup.back_mut().unwrap().total_fish += young_fish_to_promote;
+    for _i in 0..days {
+        // Add new fish
+        let new_fish_to_add = lookup[0].total_fish;
+        let young_fish_to_promote = hatchery[0].total_fish;
 
-            hatchery.rotate_left(1);
-            for (i, group) in hatchery.iter_mut().enumerate() {
-                group.generations = i;
-            }
-            hatchery.back_mut().unwrap().total_fish = new_fish_to_add;
+        lookup.rotate_left(1);
+        for (i, group) in lookup.iter_mut().enumerate() {
+            group.generations = i;
         }
+        let back = lookup.back_mut().unwrap();
+        back.total_fish =
+            back.total_fish.checked_add(young_fish_to_promote).ok_or_else(overflow)?;
 
-        let answer = lookup.iter().chain(hatchery.iter()).map(|g| g.total_fish).sum::<usize>();
-        println!("Answer: {}", answer);
-        Ok(())
+        hatchery.rotate_left(1);
+        for (i, group) in hatchery.iter_mut().enumerate() {
+            group.generations = i;
+        }
+        hatchery.back_mut().unwrap().total_fish = new_fish_to_add;
     }
+
+    let total = lookup
+        .iter()
+        .chain(hatchery.iter())
+        .try_fold(0u128, |total, g| total.checked_add(g.total_fish))
+        .ok_or_else(overflow)?;
+    Ok(total)
 }
 
 #[derive(Clone, Copy, Debug)]
 struct FishCounter {
     generations: usize,
-    total_fish: usize,
+    total_fish: u128,
 }
 
-/// The called bingo numbers
-#[derive(Debug, Clone)]
-struct LanternFish(Vec<usize>);
-
-impl FromStr for LanternFish {
-    type Err = ParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut values = vec![];
-        for v in s.split(',') {
-            values.push(v.parse::<usize>().map_err(|e| ParseError::new(e.to_string()))?);
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::commands::day6a::simulate_naive;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn prop_buckets_match_naive(
+            timers in prop::collection::vec(0..9usize, 0..20),
+            days in 0..60usize,
+        ) {
+            let fish = LanternFish(timers);
+            let naive = simulate_naive(&fish, days) as u128;
+            prop_assert_eq!(simulate_buckets(&fish, days).unwrap(), naive);
         }
-        Ok(LanternFish(values))
+    }
+
+    #[test]
+    fn test_long_simulations() {
+        let fish = LanternFish(vec![3]);
+        assert_eq!(simulate_buckets(&fish, 600).unwrap(), 53_659_029_182_581_055_724_373);
+        assert!(simulate_buckets(&fish, 5000).is_err());
     }
 }
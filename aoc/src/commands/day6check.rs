@@ -0,0 +1,60 @@
+use clap::Parser;
+
+use crate::utils::Rng;
+
+use super::{
+    day6a::{simulate_naive, LanternFish},
+    day6b::simulate_buckets,
+    CommandImpl, DynError,
+};
+
+/// Cross check the naive and bucketed Day 6 simulations on random schools of fish
+#[derive(Parser, Debug)]
+pub struct Day6Check {
+    /// Number of random starting schools to try
+    #[clap(long, short, default_value = "100")]
+    trials: usize,
+    /// Largest number of days to simulate, at most 128 as the naive engine is exponential
+    #[clap(long, default_value = "80")]
+    max_days: usize,
+    /// Largest number of fish in a starting school, at most 100
+    #[clap(long, default_value = "20")]
+    max_fish: usize,
+    #[clap(long, short, default_value = "42")]
+    seed: u64,
+}
+
+/// Largest `--max-days`, where the naive engine tracks about 95,000 fish for each starting fish
+pub const MAX_DAYS: usize = 128;
+/// Largest `--max-fish`, keeping a naive school under 10 million fish
+pub const MAX_FISH: usize = 100;
+
+impl CommandImpl for Day6Check {
+    fn main(&self) -> Result<(), DynError> {
+        if self.max_fish == 0 || self.max_fish > MAX_FISH {
+            return Err(format!("Largest school size must be from 1 to {}", MAX_FISH).into());
+        }
+        if self.max_days > MAX_DAYS {
+            return Err(format!("Largest number of days must be at most {}", MAX_DAYS).into());
+        }
+        let max_fish = self.max_fish.checked_add(1).ok_or("Largest school size is too large")?;
+        let max_days = self.max_days.checked_add(1).ok_or("Largest number of days is too large")?;
+        let mut rng = Rng::new(self.seed);
+        for trial in 0..self.trials {
+            let fish =
+                LanternFish((0..rng.gen_range(1, max_fish)).map(|_| rng.gen_range(0, 9)).collect());
+            let days = rng.gen_range(0, max_days);
+            let naive = simulate_naive(&fish, days) as u128;
+            let bucket = simulate_buckets(&fish, days)?;
+            if naive != bucket {
+                return Err(format!(
+                    "Trial {} diverged after {} days: naive={} bucket={} fish={:?}",
+                    trial, days, naive, bucket, fish.0
+                )
+                .into());
+            }
+        }
+        println!("All {} trials agree", self.trials);
+        Ok(())
+    }
+}
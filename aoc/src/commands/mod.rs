@@ -5,12 +5,16 @@ pub mod day2;
 pub mod day2b;
 pub mod day3a;
 pub mod day3b;
+pub mod day3bench;
 pub mod day4a;
 pub mod day4b;
+pub mod day4search;
 pub mod day5a;
 pub mod day5b;
 pub mod day6a;
 pub mod day6b;
+pub mod day6check;
+pub mod watch;
 
 use std::error::Error;
 
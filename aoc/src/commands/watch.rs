@@ -0,0 +1,146 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use clap::Parser;
+
+use super::{CommandImpl, DynError};
+
+/// Re-run a day whenever its input changes, showing how the answer changed and how long it took
+#[derive(Parser, Debug)]
+pub struct Watch {
+    /// Subcommand to run, e.g. day2
+    day: String,
+    #[clap(long, short)]
+    input: PathBuf,
+    /// Also rebuild with cargo and re-run when a file in aoc/src/commands changes
+    #[clap(long)]
+    rebuild: bool,
+    /// Milliseconds between checks for changes
+    #[clap(long, default_value = "500")]
+    interval: u64,
+    /// Extra arguments for the day, given after `--`
+    #[clap(last = true)]
+    args: Vec<String>,
+}
+
+impl CommandImpl for Watch {
+    fn main(&self) -> Result<(), DynError> {
+        let sources = Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("commands");
+        let mut input_seen = modified(&self.input);
+        let mut sources_seen = newest_in(&sources);
+        // Resolved before any rebuild, as afterwards it names the replaced binary on some platforms
+        let exe = env::current_exe()?;
+        let mut previous = None;
+        self.run(&exe, &mut previous)?;
+
+        loop {
+            thread::sleep(Duration::from_millis(self.interval));
+            let input_now = modified(&self.input);
+            let sources_now = if self.rebuild { newest_in(&sources) } else { sources_seen };
+            if input_now == input_seen && sources_now == sources_seen {
+                continue;
+            }
+            if sources_now != sources_seen && !self.build()? {
+                // Wait for the next change to the sources before trying again
+                sources_seen = sources_now;
+                continue;
+            }
+            input_seen = input_now;
+            sources_seen = sources_now;
+            self.run(&exe, &mut previous)?;
+        }
+    }
+}
+
+impl Watch {
+    /// Run the day as a child process of `exe`, so a rebuilt binary is picked up, and compare its
+    /// output to the `previous` run
+    fn run(&self, exe: &Path, previous: &mut Option<String>) -> Result<(), DynError> {
+        let start = Instant::now();
+        let output = Command::new(exe)
+            .arg(&self.day)
+            .arg("--input")
+            .arg(&self.input)
+            .args(&self.args)
+            .output()?;
+        let elapsed = start.elapsed();
+
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        println!("== {} ({:?}) ==", self.day, elapsed);
+        print!("{}", stdout);
+        if !output.status.success() {
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            return Ok(());
+        }
+        if let Some(previous) = previous.as_ref() {
+            let changes = diff_lines(previous, &stdout);
+            if changes.is_empty() {
+                println!("Answer unchanged");
+            } else {
+                println!("Changed:");
+                changes.iter().for_each(|change| println!("{}", change));
+            }
+        }
+        *previous = Some(stdout);
+        Ok(())
+    }
+
+    /// Run `cargo build` for this binary in the profile it was built with, returning false if
+    /// the build failed
+    fn build(&self) -> Result<bool, DynError> {
+        println!("== Rebuilding ==");
+        let mut cargo = Command::new(env::var("CARGO").unwrap_or_else(|_| String::from("cargo")));
+        cargo.current_dir(env!("CARGO_MANIFEST_DIR")).args(["build", "--bin", "aoc"]);
+        if !cfg!(debug_assertions) {
+            cargo.arg("--release");
+        }
+        let status = cargo.status()?;
+        Ok(status.success())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The latest modification time of any file directly in `dir`
+fn newest_in(dir: &Path) -> Option<SystemTime> {
+    fs::read_dir(dir).ok()?.filter_map(|entry| modified(&entry.ok()?.path())).max()
+}
+
+/// Lines that differ between two outputs, as `- old` and `+ new` pairs
+pub fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut changes = vec![];
+    for i in 0..old.len().max(new.len()) {
+        let (before, after) = (old.get(i), new.get(i));
+        if before == after {
+            continue;
+        }
+        if let Some(line) = before {
+            changes.push(format!("- {}", line));
+        }
+        if let Some(line) = after {
+            changes.push(format!("+ {}", line));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        assert!(diff_lines("Answer: 5\n", "Answer: 5\n").is_empty());
+        assert_eq!(diff_lines("a\nb\n", "a\nc\nd\n"), vec!["- b", "+ c", "+ d"]);
+        assert_eq!(diff_lines("a\nb\n", "a\n"), vec!["- b"]);
+    }
+}
@@ -0,0 +1,35 @@
+pub mod commands;
+pub mod utils;
+
+use commands::*;
+use enum_dispatch::enum_dispatch;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+pub struct Opts {
+    #[clap(subcommand)]
+    pub subcommand: SubCommand,
+}
+
+#[enum_dispatch(CommandImpl)]
+#[derive(Parser, Debug)]
+pub enum SubCommand {
+    Day0(day0::Day0),
+    Day1(day1::Day1),
+    Day1b(day1b::Day1b),
+    Day2(day2::Day2),
+    Day2b(day2b::Day2b),
+    Day3a(day3a::Day3a),
+    Day3b(day3b::Day3b),
+    Day3Bench(day3bench::Day3Bench),
+    Day4a(day4a::Day4a),
+    Day4b(day4b::Day4b),
+    Day4Search(day4search::Day4Search),
+    Day5a(day5a::Day5a),
+    Day5b(day5b::Day5b),
+    Day6a(day6a::Day6a),
+    Day6b(day6b::Day6b),
+    Day6Check(day6check::Day6Check),
+    Watch(watch::Watch),
+}
@@ -1,12 +1,37 @@
 use std::{
     error::Error,
     fmt::{self, Debug},
-    fs::File,
-    io::{BufRead, BufReader},
-    path::Path,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
+/// Failure to open or read an input file, naming the file
+#[derive(Debug)]
+pub struct FileError {
+    path: PathBuf,
+    source: io::Error,
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to read {}: {}", self.path.display(), self.source)
+    }
+}
+
+impl Error for FileError {}
+
+pub fn open_file(path: impl AsRef<Path>) -> Result<BufReader<File>, FileError> {
+    let path = path.as_ref();
+    File::open(path).map(BufReader::new).map_err(|source| FileError { path: path.into(), source })
+}
+
+pub fn read_to_string(path: impl AsRef<Path>) -> Result<String, FileError> {
+    let path = path.as_ref();
+    fs::read_to_string(path).map_err(|source| FileError { path: path.into(), source })
+}
+
 #[derive(Debug, Clone)]
 pub struct SlurpError {
     line: usize,
@@ -22,17 +47,19 @@ impl fmt::Display for SlurpError {
 impl Error for SlurpError {}
 
 #[allow(clippy::missing_errors_doc)]
-pub fn slurp_file<P, T>(path: P) -> Result<Vec<T>, SlurpError>
+pub fn slurp_file<P, T>(path: P) -> Result<Vec<T>, Box<dyn Error>>
 where
     P: AsRef<Path>,
     T: FromStr,
     <T as FromStr>::Err: Error,
 {
-    let reader = File::open(&path).map(BufReader::new).expect("Failed to open file");
+    let reader = open_file(path)?;
     let mut result = vec![];
     for (i, line) in reader.lines().enumerate() {
-        let line = line.map_err(|e| SlurpError { line: i, msg: e.to_string() })?;
-        result.push(line.parse::<T>().map_err(|e| SlurpError { line: i, msg: e.to_string() })?);
+        let line = line.map_err(|e| SlurpError { line: i + 1, msg: e.to_string() })?;
+        let value =
+            line.parse::<T>().map_err(|e| SlurpError { line: i + 1, msg: e.to_string() })?;
+        result.push(value);
     }
     Ok(result)
 }
@@ -54,3 +81,36 @@ impl fmt::Display for ParseError {
         write!(f, "Error for command: {}", self.msg)
     }
 }
+
+/// A small seedable PRNG (splitmix64) for generating reproducible random inputs.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `[low, high)`.
+    pub fn gen_range(&mut self, low: usize, high: usize) -> usize {
+        assert!(low < high, "Empty range {}..{}", low, high);
+        low + (self.next_u64() % (high - low) as u64) as usize
+    }
+
+    /// Shuffles `values` in place with a Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            values.swap(i, self.gen_range(0, i + 1));
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Every day should reject bad input with a message and a non-zero exit code, never a panic.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+const DAYS: &[&[&str]] = &[
+    &["day1"],
+    &["day1b"],
+    &["day2"],
+    &["day2b"],
+    &["day3a"],
+    &["day3b"],
+    &["day3-bench"],
+    &["day4a"],
+    &["day4b"],
+    &["day4-search", "--board", "0"],
+    &["day5a"],
+    &["day5b"],
+    &["day6a"],
+    &["day6b"],
+];
+
+const GARBAGE: &[&[u8]] = &[
+    b"not a puzzle input\n",
+    b"1,2,3 -> 4\n-7 x\n",
+    b"\x00\xff\xfe\n\n,,\n",
+    b"99999999999999999999999999\n",
+];
+
+/// Inputs that overflow, leave the grid or expand without bound, with whether the day should
+/// still succeed
+const OUT_OF_RANGE: &[(&[&str], &str, bool)] = &[
+    (&["day1"], "9223372036854775807\n9223372036854775807\n-9223372036854775808\n", true),
+    (&["day1b"], "9223372036854775807\n9223372036854775807\n9223372036854775807\n", true),
+    (&["day2"], "forward 9223372036854775807\nforward 1\n", false),
+    (&["day2"], "down 9223372036854775807\nforward 2\n", false),
+    (&["day2b"], "down 5000000000\nforward 5000000000\n", false),
+    (&["day2b"], "back -9223372036854775808\n", false),
+    (&["day2"], "repeat 18446744073709551615 { forward 1 }\n", false),
+    (&["day2"], "repeat 18446744073709551615 { }\n", false),
+    (
+        &["day4a", "--lenient"],
+        "18446744073709551615,1\n\n18446744073709551615 1\n3 18446744073709551614\n",
+        false,
+    ),
+    (&["day4b", "--lenient"], "1,2\n\n1 100000\n", false),
+    (&["day5a"], "-5,0 -> -1,0\n-3,0 -> -3,5\n", false),
+    (&["day5b"], "0,0 -> 1000000000,1000000000\n", false),
+    (&["day5b"], "0,0 -> 9223372036854775807,0\n", false),
+    (&["day5b"], "0,0 -> 2,1\n", false),
+    (&["day6b", "--days", "600"], "3\n", true),
+    (&["day6b", "--days", "2000"], "3\n", false),
+    (&["day6a", "--days", "100000"], "3\n", false),
+];
+
+/// Run a day on `input`, failing the test if it hangs
+fn run(day: &[&str], input: &Path) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_aoc"))
+        .args(day)
+        .arg("--input")
+        .arg(input)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run aoc");
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("{:?} on {} did not finish in 30 seconds", day, input.display());
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    child.wait_with_output().unwrap()
+}
+
+fn write_input(name: &str, contents: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("aoc-bad-input-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn assert_no_panic(day: &[&str], output: &Output) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("panicked"), "{:?} panicked: {}", day, stderr);
+    if !output.status.success() {
+        assert_eq!(output.status.code(), Some(1), "{:?}: {}", day, stderr);
+        assert!(!stderr.trim().is_empty(), "{:?} failed without a message", day);
+    }
+}
+
+#[test]
+fn test_empty_input() {
+    // An empty depth series or course is valid, an empty report, deck or school is not
+    let path = write_input("empty", b"");
+    for day in DAYS {
+        assert_no_panic(day, &run(day, &path));
+    }
+}
+
+#[test]
+fn test_garbage_input() {
+    for (i, garbage) in GARBAGE.iter().enumerate() {
+        let path = write_input(&format!("garbage{}", i), garbage);
+        for day in DAYS {
+            let output = run(day, &path);
+            assert_no_panic(day, &output);
+            assert!(!output.status.success(), "{:?} accepted {:?}", day, garbage);
+        }
+    }
+}
+
+#[test]
+fn test_out_of_range_input() {
+    for (i, (day, input, succeeds)) in OUT_OF_RANGE.iter().enumerate() {
+        let path = write_input(&format!("range{}", i), input.as_bytes());
+        let output = run(day, &path);
+        assert_no_panic(day, &output);
+        assert_eq!(output.status.success(), *succeeds, "{:?} on {:?}", day, input);
+    }
+
+    // Blocks nested far deeper than any script needs
+    let path = write_input("nested", "repeat 1 {\n".repeat(200_000).as_bytes());
+    let output = run(&["day2"], &path);
+    assert_no_panic(&["day2"], &output);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_missing_input() {
+    let path = env::temp_dir().join("aoc-bad-input-does-not-exist");
+    for day in DAYS {
+        let output = run(day, &path);
+        assert_no_panic(day, &output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("Failed to read"), "{:?}: {}", day, stderr);
+    }
+}
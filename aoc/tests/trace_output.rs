@@ -0,0 +1,79 @@
+//! A Day 2 trace written to stdout must be the only thing on stdout, so it can be redirected to a
+//! file and parsed.
+
+use std::{
+    env, fs,
+    process::{Command, Output},
+};
+
+const COURSE: &str = "forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2\n";
+
+fn trace(format: &str) -> Output {
+    let path = env::temp_dir().join(format!("aoc-trace-{}-{}", std::process::id(), format));
+    fs::write(&path, COURSE).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_aoc"))
+        .args(["day2", "--input"])
+        .arg(&path)
+        .args(["--summary", "--trace", format])
+        .output()
+        .expect("Failed to run aoc");
+    fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+/// The fields of one `{"key": value, ...}` object, values unquoted
+fn json_object(line: &str) -> Vec<(String, String)> {
+    let body = line.strip_prefix('{').and_then(|l| l.strip_suffix('}'));
+    let body = body.unwrap_or_else(|| panic!("Not an object: {:?}", line));
+    body.split(", ")
+        .map(|field| {
+            let (key, value) = field.split_once(": ").expect("Missing value");
+            let key = key.strip_prefix('"').and_then(|k| k.strip_suffix('"')).expect("Bad key");
+            (key.to_string(), value.trim_matches('"').to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn test_json_trace_on_stdout() {
+    let output = trace("json");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!((lines.first(), lines.last()), (Some(&"["), Some(&"]")), "{}", stdout);
+
+    let objects = &lines[1..lines.len() - 1];
+    assert_eq!(objects.len(), COURSE.lines().count());
+    for (i, line) in objects.iter().enumerate() {
+        let line = line.trim();
+        let line = if i + 1 < objects.len() {
+            line.strip_suffix(',').expect("Missing comma between objects")
+        } else {
+            line
+        };
+        let keys: Vec<String> = json_object(line).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["step", "command", "x", "depth", "aim"]);
+    }
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().last(), Some("150"));
+}
+
+#[test]
+fn test_csv_trace_on_stdout() {
+    let output = trace("csv");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("step,command,x,depth,aim"));
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    assert_eq!(rows.len(), COURSE.lines().count());
+    for (i, row) in rows.iter().enumerate() {
+        assert_eq!(row.len(), 5, "{:?}", row);
+        assert_eq!(row[0], (i + 1).to_string());
+        assert!(row[2..].iter().all(|value| value.parse::<i64>().is_ok()), "{:?}", row);
+    }
+    assert_eq!(rows.last().unwrap()[2..], ["15", "10", "0"]);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().last(), Some("150"));
+}
@@ -0,0 +1,268 @@
+//! Puzzle input downloads and the local cache they are kept in.
+//!
+//! Inputs are fetched once per day into `inputs/YEAR/dayN.txt`, with a checksum of each recorded
+//! in `checksums.txt` alongside them. A cached input is never fetched again, and one that no
+//! longer matches its checksum is reported rather than silently replaced.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::DynError;
+
+/// Where puzzle inputs come from
+pub trait Backend {
+    fn fetch(&self, year: u32, day: u32) -> Result<Vec<u8>, DynError>;
+}
+
+/// Downloads inputs over HTTP with `curl`, authenticated by the session cookie
+pub struct Curl {
+    pub base_url: String,
+    pub session: String,
+}
+
+impl Backend for Curl {
+    fn fetch(&self, year: u32, day: u32) -> Result<Vec<u8>, DynError> {
+        check_session(&self.session)?;
+        let url = format!("{}/{}/day/{}/input", self.base_url.trim_end_matches('/'), year, day);
+        // The cookie goes through stdin so the token doesn't show up in the process list
+        let mut child = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--config", "-", &url])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run curl: {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // Quoted config values treat `\` as an escape, so escape it along with `"`
+            let session = self.session.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(stdin, "cookie = \"session={}\"", session)?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Failed to fetch {}: {}", url, stderr.trim()).into());
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Settings read from `key = value` lines in the config file, then overridden by `AOC_*`
+/// environment variables
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub session: Option<String>,
+    pub base_url: String,
+    pub year: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { session: None, base_url: String::from("https://adventofcode.com"), year: 2021 }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, DynError> {
+        let mut config = match fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e).into()),
+        };
+        if let Ok(session) = env::var("AOC_SESSION") {
+            check_session(&session).map_err(|e| format!("AOC_SESSION: {}", e))?;
+            config.session = Some(session);
+        }
+        if let Ok(base_url) = env::var("AOC_BASE_URL") {
+            config.base_url = base_url;
+        }
+        Ok(config)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, DynError> {
+        let mut config = Self::default();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Config line {}: expected key = value", i + 1))?;
+            let value = value.trim().trim_matches('"').to_string();
+            match key.trim() {
+                "session" => {
+                    check_session(&value).map_err(|e| format!("Config line {}: {}", i + 1, e))?;
+                    config.session = Some(value)
+                }
+                "base_url" => config.base_url = value,
+                "year" => {
+                    config.year =
+                        value.parse().map_err(|e| format!("Config line {}: {}", i + 1, e))?
+                }
+                other => {
+                    return Err(format!("Config line {}: unknown key {:?}", i + 1, other).into())
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// A line break in the token would end the curl config line and start another option
+fn check_session(session: &str) -> Result<(), String> {
+    if session.contains(&['\r', '\n'][..]) {
+        return Err(String::from("session token contains a line break"));
+    }
+    Ok(())
+}
+
+/// The directory of fetched inputs along with their checksums
+pub struct InputCache {
+    dir: PathBuf,
+}
+
+impl InputCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn path(&self, day: u32) -> PathBuf {
+        self.dir.join(format!("day{}.txt", day))
+    }
+
+    /// The cached input for `day`, fetching it from `backend` only if it isn't cached yet
+    pub fn get(&self, year: u32, day: u32, backend: &dyn Backend) -> Result<PathBuf, DynError> {
+        let path = self.path(day);
+        let name = format!("day{}.txt", day);
+        let mut checksums = self.checksums()?;
+
+        if path.exists() {
+            let actual = checksum(&fs::read(&path)?);
+            return match checksums.get(&name) {
+                Some(&expected) if expected == actual => Ok(path),
+                Some(_) => Err(format!(
+                    "{} does not match its checksum, delete it to fetch it again",
+                    path.display()
+                )
+                .into()),
+                None => Err(format!(
+                    "{} has no recorded checksum, delete it to fetch it again",
+                    path.display()
+                )
+                .into()),
+            };
+        }
+
+        let input = backend.fetch(year, day)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&path, &input)?;
+        checksums.insert(name, checksum(&input));
+        let manifest: String =
+            checksums.iter().map(|(name, sum)| format!("{} {:016x}\n", name, sum)).collect();
+        fs::write(self.dir.join("checksums.txt"), manifest)?;
+        Ok(path)
+    }
+
+    fn checksums(&self) -> Result<BTreeMap<String, u64>, DynError> {
+        let manifest = match fs::read_to_string(self.dir.join("checksums.txt")) {
+            Ok(manifest) => manifest,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        manifest
+            .lines()
+            .map(|line| {
+                let (name, sum) = line.split_once(' ').ok_or("Malformed checksums.txt")?;
+                Ok((name.to_string(), u64::from_str_radix(sum, 16)?))
+            })
+            .collect()
+    }
+}
+
+/// 64 bit FNV-1a, enough to notice a truncated or edited input
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{cell::Cell, io::Read, net::TcpListener, thread};
+
+    struct Mock {
+        calls: Cell<usize>,
+    }
+
+    impl Backend for Mock {
+        fn fetch(&self, _year: u32, day: u32) -> Result<Vec<u8>, DynError> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(format!("input for day {}\n", day).into_bytes())
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("xtask-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_cache_never_refetches() {
+        let cache = InputCache::new(temp_dir("cache"));
+        let mock = Mock { calls: Cell::new(0) };
+        let path = cache.get(2021, 3, &mock).unwrap();
+        assert_eq!(cache.get(2021, 3, &mock).unwrap(), path);
+        assert_eq!(mock.calls.get(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "input for day 3\n");
+
+        fs::write(&path, "edited").unwrap();
+        assert!(cache.get(2021, 3, &mock).is_err());
+        assert_eq!(mock.calls.get(), 1);
+    }
+
+    #[test]
+    fn test_config() {
+        let config = Config::parse("# comment\nsession = \"abc\"\nbase_url=http://x\n").unwrap();
+        assert_eq!(config.session.as_deref(), Some("abc"));
+        assert_eq!((config.base_url.as_str(), config.year), ("http://x", 2021));
+        assert!(Config::parse("token = abc").is_err());
+        assert_eq!(
+            Config::parse("session = a\rurl = x").unwrap_err().to_string(),
+            "Config line 1: session token contains a line break"
+        );
+    }
+
+    #[test]
+    fn test_curl_against_mock_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..len]).into_owned();
+            let body = "1\n2\n3\n";
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+                .unwrap();
+            request
+        });
+
+        let curl = Curl { base_url, session: String::from("se\"c\\ret") };
+        let cache = InputCache::new(temp_dir("curl"));
+        let path = cache.get(2021, 1, &curl).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "1\n2\n3\n");
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /2021/day/1/input "), "{}", request);
+        assert!(request.contains("Cookie: session=se\"c\\ret"), "{}", request);
+
+        let curl = Curl { base_url: String::from("http://x"), session: String::from("a\nurl=y") };
+        assert!(curl.fetch(2021, 1).is_err());
+    }
+}
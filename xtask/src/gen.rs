@@ -0,0 +1,321 @@
+//! Deterministic puzzle input generators.
+//!
+//! Each generator also solves its own input with a deliberately simple reference solution, so
+//! the answers printed alongside an input can be checked against the real commands.
+
+use std::{collections::HashSet, fmt::Write};
+
+use aoc::utils::Rng;
+
+/// A generated input along with the answers to both parts of the puzzle
+pub struct Generated {
+    pub input: String,
+    pub answers: [String; 2],
+}
+
+/// Generate an input for `day` with roughly `size` records
+pub fn generate(day: u32, seed: u64, size: usize) -> Result<Generated, String> {
+    let mut rng = Rng::new(seed);
+    match day {
+        1 => Ok(depths(&mut rng, size)),
+        2 => course(&mut rng, size),
+        3 => diagnostics(&mut rng, size),
+        4 => Ok(bingo(&mut rng, size)),
+        5 => Ok(vents(&mut rng, size)),
+        6 => Ok(fish(&mut rng, size)),
+        _ => Err(format!("No generator for day {}, days 1 to 6 are supported", day)),
+    }
+}
+
+/// Day 1, a random walk of mostly increasing depths
+fn depths(rng: &mut Rng, size: usize) -> Generated {
+    let mut depth = rng.gen_range(100, 200) as i64;
+    let mut series = vec![];
+    for _ in 0..size {
+        series.push(depth);
+        depth = (depth + rng.gen_range(0, 51) as i64 - 20).abs();
+    }
+    let increases = |window: usize| {
+        let sums: Vec<i64> = series.windows(window).map(|w| w.iter().sum()).collect();
+        sums.windows(2).filter(|w| w[1] > w[0]).count()
+    };
+
+    let mut input = String::new();
+    series.iter().for_each(|d| writeln!(input, "{}", d).unwrap());
+    Generated { answers: [increases(1).to_string(), increases(3).to_string()], input }
+}
+
+/// Day 2, commands that never take the submarine above the surface, failing like the solver
+/// when a position or answer doesn't fit in 64 bits
+fn course(rng: &mut Rng, size: usize) -> Result<Generated, String> {
+    let overflow = || format!("A day 2 course of {} commands overflows 64 bits", size);
+    let (mut x, mut depth, mut aim_depth) = (0i64, 0i64, 0i64);
+    let mut input = String::new();
+    for _ in 0..size {
+        let mag = rng.gen_range(1, 10) as i64;
+        // Under both models `down` and `up` move the same value, depth or aim
+        let cmd = match rng.gen_range(0, 3) {
+            0 => "forward",
+            1 => "down",
+            _ if depth >= mag => "up",
+            _ => "down",
+        };
+        match cmd {
+            "forward" => {
+                x += mag;
+                aim_depth = depth
+                    .checked_mul(mag)
+                    .and_then(|d| aim_depth.checked_add(d))
+                    .ok_or_else(overflow)?;
+            }
+            "down" => depth += mag,
+            _ => depth -= mag,
+        }
+        writeln!(input, "{} {}", cmd, mag).unwrap();
+    }
+    let answer = |y: i64| x.checked_mul(y).map(|a| a.to_string()).ok_or_else(overflow);
+    Ok(Generated { answers: [answer(depth)?, answer(aim_depth)?], input })
+}
+
+/// Day 3, distinct binary lines, at least 12 bits wide and wider when `size` needs it
+fn diagnostics(rng: &mut Rng, size: usize) -> Result<Generated, String> {
+    let mut width = 12;
+    while (1u64 << width) < 2 * size as u64 {
+        width += 1;
+    }
+    let mut seen = HashSet::new();
+    let mut lines = vec![];
+    while lines.len() < size {
+        let line = rng.next_u64() & ((1 << width) - 1);
+        if seen.insert(line) {
+            lines.push(line);
+        }
+    }
+
+    let bit = |line: u64, i: usize| line >> (width - 1 - i) & 1 == 1;
+    let (mut gamma, mut epsilon) = (0u64, 0u64);
+    for i in 0..width {
+        let ones = lines.iter().filter(|&&l| bit(l, i)).count();
+        if ones > lines.len() - ones {
+            gamma |= 1 << (width - 1 - i);
+        } else {
+            epsilon |= 1 << (width - 1 - i);
+        }
+    }
+    let rating = |most_common: bool| {
+        let mut remaining = lines.clone();
+        for i in 0..width {
+            if remaining.len() <= 1 {
+                break;
+            }
+            let ones = remaining.iter().filter(|&&l| bit(l, i)).count();
+            let zeros = remaining.len() - ones;
+            // A bit every remaining line shares is kept whichever rating this is
+            let keep_ones =
+                if ones == 0 || zeros == 0 { zeros == 0 } else { (ones >= zeros) == most_common };
+            remaining.retain(|&l| bit(l, i) == keep_ones);
+        }
+        match remaining.as_slice() {
+            [line] => Ok(*line),
+            _ => Err(format!("{} lines are left for a day 3 rating", remaining.len())),
+        }
+    };
+
+    let mut input = String::new();
+    lines.iter().for_each(|l| writeln!(input, "{:0width$b}", l, width = width).unwrap());
+    let answers = [(gamma * epsilon).to_string(), (rating(true)? * rating(false)?).to_string()];
+    Ok(Generated { answers, input })
+}
+
+/// Day 4, every number from 0 to 99 drawn once and `size` boards of distinct numbers
+fn bingo(rng: &mut Rng, size: usize) -> Generated {
+    let mut draws: Vec<i64> = (0..100).collect();
+    rng.shuffle(&mut draws);
+    let boards: Vec<Vec<i64>> = (0..size)
+        .map(|_| {
+            let mut values: Vec<i64> = (0..100).collect();
+            rng.shuffle(&mut values);
+            values.truncate(25);
+            values
+        })
+        .collect();
+
+    // Each board wins on the draw completing its earliest line, ties go to the lower board
+    let mut drawn_at = [0; 100];
+    draws.iter().enumerate().for_each(|(n, &d)| drawn_at[d as usize] = n);
+    let mut wins: Vec<(usize, i64)> = boards
+        .iter()
+        .map(|board| {
+            let at = |i: usize| drawn_at[board[i] as usize];
+            let rows = (0..5).map(|r| (0..5).map(|c| at(r * 5 + c)).max().unwrap());
+            let columns = (0..5).map(|c| (0..5).map(|r| at(r * 5 + c)).max().unwrap());
+            let win = rows.chain(columns).min().unwrap();
+            let unmarked: i64 = (0..25).filter(|&i| at(i) > win).map(|i| board[i]).sum();
+            (win, unmarked * draws[win])
+        })
+        .collect();
+    wins.sort_by_key(|&(win, _)| win);
+    let scores: Vec<i64> = wins.iter().map(|&(_, score)| score).collect();
+
+    let mut input = String::new();
+    let draws: Vec<String> = draws.iter().map(|d| d.to_string()).collect();
+    writeln!(input, "{}", draws.join(",")).unwrap();
+    for board in &boards {
+        writeln!(input).unwrap();
+        for row in board.chunks(5) {
+            let row: Vec<String> = row.iter().map(|v| format!("{:2}", v)).collect();
+            writeln!(input, "{}", row.join(" ")).unwrap();
+        }
+    }
+    let answer = |score: Option<&i64>| score.map_or(String::from("none"), |s| s.to_string());
+    Generated { answers: [answer(scores.first()), answer(scores.last())], input }
+}
+
+/// Day 5, horizontal, vertical and diagonal lines on a 1000 by 1000 grid
+fn vents(rng: &mut Rng, size: usize) -> Generated {
+    const GRID: usize = 1000;
+    let mut lines = vec![];
+    while lines.len() < size {
+        let (x1, y1) = (rng.gen_range(0, GRID) as i64, rng.gen_range(0, GRID) as i64);
+        let len = rng.gen_range(1, 200) as i64;
+        let (dx, dy) = match rng.gen_range(0, 3) {
+            0 => (1, 0),
+            1 => (0, 1),
+            _ => (1, if rng.gen_range(0, 2) == 0 { 1 } else { -1 }),
+        };
+        let (dx, dy) = if rng.gen_range(0, 2) == 0 { (dx, dy) } else { (-dx, -dy) };
+        let (x2, y2) = (x1 + dx * len, y1 + dy * len);
+        if (0..GRID as i64).contains(&x2) && (0..GRID as i64).contains(&y2) {
+            lines.push((x1, y1, x2, y2));
+        }
+    }
+
+    let overlaps = |diagonals: bool| {
+        let mut grid = vec![0u8; GRID * GRID];
+        for &(x1, y1, x2, y2) in &lines {
+            if x1 != x2 && y1 != y2 && !diagonals {
+                continue;
+            }
+            let (dx, dy) = ((x2 - x1).signum(), (y2 - y1).signum());
+            let steps = (x2 - x1).abs().max((y2 - y1).abs());
+            for i in 0..=steps {
+                let cell = &mut grid[((y1 + dy * i) * GRID as i64 + x1 + dx * i) as usize];
+                *cell = cell.saturating_add(1);
+            }
+        }
+        grid.iter().filter(|&&c| c >= 2).count()
+    };
+
+    let mut input = String::new();
+    for (x1, y1, x2, y2) in &lines {
+        writeln!(input, "{},{} -> {},{}", x1, y1, x2, y2).unwrap();
+    }
+    Generated { answers: [overlaps(false).to_string(), overlaps(true).to_string()], input }
+}
+
+/// Day 6, fish with timers from 1 to 5 like the puzzle inputs
+fn fish(rng: &mut Rng, size: usize) -> Generated {
+    let timers: Vec<usize> = (0..size.max(1)).map(|_| rng.gen_range(1, 6)).collect();
+    let population = |days: usize| {
+        let mut counts = [0u128; 9];
+        timers.iter().for_each(|&t| counts[t] += 1);
+        for _ in 0..days {
+            counts.rotate_left(1);
+            counts[6] += counts[8];
+        }
+        counts.iter().sum::<u128>()
+    };
+
+    let timers: Vec<String> = timers.iter().map(|t| t.to_string()).collect();
+    let input = format!("{}\n", timers.join(","));
+    Generated { answers: [population(80).to_string(), population(256).to_string()], input }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aoc::{
+        commands::{day1, day2, day3a, day3b, day4a, day5a, day6a, day6b},
+        utils::slurp_file,
+    };
+    use std::{env, fs, path::Path};
+
+    /// Both answers for `day` from the real solvers
+    fn solve(day: u32, path: &Path) -> [String; 2] {
+        match day {
+            1 => {
+                let increases = |size| {
+                    let (mut count, mut prev) = (0, None);
+                    day1::for_each_sum(path, size, |sum| {
+                        if prev.map_or(false, |prev| day1::Compare::Increase.matches(prev, sum)) {
+                            count += 1;
+                        }
+                        prev = Some(sum);
+                    })
+                    .unwrap();
+                    count.to_string()
+                };
+                [increases(1), increases(3)]
+            }
+            2 => [day2::Model::Direct, day2::Model::Aim]
+                .map(|model| day2::run_file(path, model).unwrap().answer().unwrap().to_string()),
+            3 => {
+                let (lines, width) = day3a::read_report(path, None).unwrap();
+                let counts = day3a::ColumnCounts::from_lines(&lines, width);
+                let (gamma, epsilon) = day3a::gamma_epsilon(&counts);
+                let report = day3a::BitSlicedReport::new(&lines, width);
+                let rating = |criterion, tie_break| {
+                    let criteria = day3b::BitCriteria {
+                        criterion,
+                        tie_break,
+                        order: day3b::BitOrder::MsbFirst,
+                    };
+                    criteria.rate(&lines, &report).value.unwrap().to_biguint()
+                };
+                let oxygen = rating(day3b::Criterion::MostCommon, day3b::TieBreak::PreferOne);
+                let co2 = rating(day3b::Criterion::LeastCommon, day3b::TieBreak::PreferZero);
+                [
+                    (gamma.to_biguint() * epsilon.to_biguint()).to_string(),
+                    (oxygen * co2).to_string(),
+                ]
+            }
+            4 => {
+                let (numbers, boards) = day4a::read_file(path, false).unwrap();
+                let results = day4a::BingoHall::new(boards).play(&numbers.0);
+                let score = |r: Option<&day4a::BoardResult>| {
+                    r.map_or(String::from("none"), |r| r.score.to_string())
+                };
+                [score(results.first()), score(results.last())]
+            }
+            5 => {
+                let lines: Vec<day5a::Line> = slurp_file(path).unwrap();
+                [false, true].map(|diagonals| day5a::count_overlaps(&lines, diagonals).to_string())
+            }
+            6 => {
+                let fish = day6a::read_fish(path).unwrap();
+                [80, 256].map(|days| day6b::simulate_buckets(&fish, days).unwrap().to_string())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_answers_match_solvers() {
+        let path = env::temp_dir().join(format!("xtask-gen-{}", std::process::id()));
+        for day in 1..=6 {
+            for seed in 1..=8 {
+                for size in [3, 10, 100, 1000] {
+                    let generated = generate(day, seed, size).unwrap();
+                    fs::write(&path, &generated.input).unwrap();
+                    let answers = solve(day, &path);
+                    assert_eq!(
+                        answers, generated.answers,
+                        "day {} seed {} size {}",
+                        day, seed, size
+                    );
+                }
+            }
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,9 @@
+mod fetch;
+mod gen;
+
 use enum_dispatch::enum_dispatch;
 use std::env;
+use std::fs;
 use std::path::Path;
 use std::process::Command;
 use std::{error::Error, path::PathBuf};
@@ -23,6 +27,8 @@ struct Opts {
 #[derive(Parser, Debug)]
 enum SubCommand {
     NewDay(NewDay),
+    Gen(Gen),
+    Fetch(Fetch),
 }
 fn main() -> Result<(), DynError> {
     let opts = Opts::parse();
@@ -60,6 +66,68 @@ impl CommandImpl for NewDay {
     }
 }
 
+#[derive(Parser, Debug)]
+struct Gen {
+    /// Day to generate an input for
+    #[clap(long, short)]
+    day: u32,
+    /// The same seed always produces the same input
+    #[clap(long, short, default_value = "0")]
+    seed: u64,
+    /// Number of records, e.g. depths, commands, lines, boards or fish
+    #[clap(long, short = 'k', default_value = "1000")]
+    size: usize,
+    /// File to write the input to, defaults to stdout
+    #[clap(long, short)]
+    output: Option<PathBuf>,
+}
+
+impl CommandImpl for Gen {
+    fn main(&self) -> Result<(), DynError> {
+        let generated = gen::generate(self.day, self.seed, self.size)?;
+        match &self.output {
+            Some(path) => fs::write(path, &generated.input)?,
+            None => print!("{}", generated.input),
+        }
+        eprintln!("Part 1: {}", generated.answers[0]);
+        eprintln!("Part 2: {}", generated.answers[1]);
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+struct Fetch {
+    /// Day to fetch the input for
+    #[clap(long, short)]
+    day: u32,
+    /// Defaults to the year in the config, or 2021
+    #[clap(long, short)]
+    year: Option<u32>,
+    /// Server to fetch from instead of the one in the config, e.g. a local mock
+    #[clap(long)]
+    base_url: Option<String>,
+    /// File of `key = value` lines setting `session`, `base_url` and `year`
+    #[clap(long, short)]
+    config: Option<PathBuf>,
+}
+
+impl CommandImpl for Fetch {
+    fn main(&self) -> Result<(), DynError> {
+        let config_path = self.config.clone().unwrap_or_else(|| project_root().join(".aoc-config"));
+        let config = fetch::Config::load(&config_path)?;
+        let session = config.session.ok_or_else(|| {
+            format!("No session token, set `session` in {} or AOC_SESSION", config_path.display())
+        })?;
+        let backend =
+            fetch::Curl { base_url: self.base_url.clone().unwrap_or(config.base_url), session };
+        let year = self.year.unwrap_or(config.year);
+        let cache = fetch::InputCache::new(project_root().join("inputs").join(year.to_string()));
+        let path = cache.get(year, self.day, &backend)?;
+        println!("{}", path.display());
+        Ok(())
+    }
+}
+
 fn project_root() -> PathBuf {
     Path::new(&env!("CARGO_MANIFEST_DIR")).ancestors().nth(1).unwrap().to_path_buf()
 }